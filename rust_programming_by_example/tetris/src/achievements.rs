@@ -0,0 +1,148 @@
+use crate::events::GameListener;
+use crate::game_board::{ClearKind, GameEvent, Tetris, FRAMES_PER_SECOND, MAX_LEVEL};
+use crate::stats::Stats;
+
+use std::cell::RefCell;
+
+// What has to happen during a game for an achievement to be unlocked, checked
+// against the statistics fed by the engine events.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Condition {
+    Clears { kind: ClearKind, count: u32 },
+    Combo(u32),
+    PerfectClears(u32),
+    // levels gained since the start of the game
+    LevelUps(u32),
+    Lines(u32),
+    // lines cleared within a number of frames from the start of a sprint
+    LinesWithin { lines: u32, frames: u32 },
+}
+
+pub struct Achievement {
+    // kept in the profiles, never to be changed
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub condition: Condition,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 9] = [
+    Achievement {
+        id: "first-tetris",
+        name: "First Tetris",
+        description: "Clear four lines at once",
+        condition: Condition::Clears { kind: ClearKind::Tetris, count: 1 },
+    },
+    Achievement {
+        id: "tetris-master",
+        name: "Tetris master",
+        description: "Clear four lines at once 10 times in a game",
+        condition: Condition::Clears { kind: ClearKind::Tetris, count: 10 },
+    },
+    Achievement {
+        id: "t-spin-double",
+        name: "Spinner",
+        description: "Clear two lines with a T-spin",
+        condition: Condition::Clears { kind: ClearKind::TSpinDouble, count: 1 },
+    },
+    Achievement {
+        id: "t-spin-triple",
+        name: "T-spin triple",
+        description: "Clear three lines with a T-spin",
+        condition: Condition::Clears { kind: ClearKind::TSpinTriple, count: 1 },
+    },
+    Achievement {
+        id: "combo-10",
+        name: "10-combo",
+        description: "Clear lines with 11 pieces in a row",
+        condition: Condition::Combo(10),
+    },
+    Achievement {
+        id: "perfect-clear",
+        name: "Perfect clear",
+        description: "Leave the board empty after clearing lines",
+        condition: Condition::PerfectClears(1),
+    },
+    Achievement {
+        id: "top-speed",
+        name: "Top speed",
+        description: "Reach the last level from the first one",
+        condition: Condition::LevelUps(MAX_LEVEL - 1),
+    },
+    Achievement {
+        id: "century",
+        name: "Century",
+        description: "Clear 100 lines in a game",
+        condition: Condition::Lines(100),
+    },
+    Achievement {
+        id: "sprint-60",
+        name: "Sprinter",
+        description: "Finish a sprint without modifiers in less than 60 seconds",
+        condition: Condition::LinesWithin { lines: 40, frames: 60 * FRAMES_PER_SECOND },
+    },
+];
+
+// Follows a game through its events, handing over every achievement whose
+// condition gets met (see `take_unlocked`). Taking placements back would make
+// them too easy, nothing counts anymore once it has been done.
+pub struct AchievementTracker<'a> {
+    // subscribed before the tracker, so it's up to date with the event
+    stats: &'a RefCell<Stats>,
+    start_level: u32,
+    // whether the game is a sprint without modifiers, the only one timed
+    sprint: bool,
+    rewound: bool,
+    // ids of the achievements already met during the game
+    met: Vec<&'static str>,
+    unlocked: Vec<&'static Achievement>,
+}
+
+impl<'a> AchievementTracker<'a> {
+    pub fn new(tetris: &Tetris, stats: &'a RefCell<Stats>, sprint: bool) -> AchievementTracker<'a> {
+        AchievementTracker {
+            stats: stats,
+            start_level: tetris.current_level,
+            sprint: sprint,
+            rewound: false,
+            met: Vec::new(),
+            unlocked: Vec::new(),
+        }
+    }
+
+    fn is_met(&self, condition: Condition, tetris: &Tetris, stats: &Stats) -> bool {
+        match condition {
+            Condition::Clears { kind, count } => stats.clears[kind as usize] >= count,
+            Condition::Combo(combo) => stats.max_combo >= combo,
+            Condition::PerfectClears(count) => stats.perfect_clears >= count,
+            Condition::LevelUps(levels) => tetris.current_level.saturating_sub(self.start_level) >= levels,
+            Condition::Lines(lines) => tetris.nb_lines >= lines,
+            Condition::LinesWithin { lines, frames } => {
+                self.sprint && tetris.nb_lines >= lines && stats.frames <= frames
+            },
+        }
+    }
+
+    // the achievements met since the last call
+    pub fn take_unlocked(&mut self) -> Vec<&'static Achievement> {
+        std::mem::take(&mut self.unlocked)
+    }
+}
+
+impl<'a> GameListener for AchievementTracker<'a> {
+    fn on_event(&mut self, tetris: &Tetris, event: &GameEvent) {
+        if let GameEvent::Rewound = *event {
+            self.rewound = true;
+        }
+        if self.rewound {
+            return
+        }
+        let stats = self.stats.borrow();
+        for achievement in ACHIEVEMENTS.iter() {
+            if !self.met.contains(&achievement.id) && self.is_met(achievement.condition, tetris, &stats) {
+                self.met.push(achievement.id);
+                self.unlocked.push(achievement);
+            }
+        }
+    }
+}
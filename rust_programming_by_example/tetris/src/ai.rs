@@ -0,0 +1,162 @@
+use crate::board::Board;
+use crate::game_board::{Input, Tetris};
+use crate::tetrimino::Tetrimino;
+
+// How much each board feature counts when choosing a placement. The
+// strategies only differ by their weights.
+#[derive(Clone, Copy)]
+pub struct Weights {
+    pub height: f64,
+    pub lines: f64,
+    pub holes: f64,
+    pub bumpiness: f64,
+}
+
+// found by people tuning their own bots
+pub const DEFAULT_WEIGHTS: Weights = Weights { height: -0.510066, lines: 0.760666, holes: -0.35663, bumpiness: -0.184483 };
+
+pub const STRATEGIES: [(&'static str, Weights); 3] = [
+    ("default", DEFAULT_WEIGHTS),
+    // keeps the surface flat, even if it means leaving holes
+    ("flat", Weights { height: -0.3, lines: 0.5, holes: -0.2, bumpiness: -0.6 }),
+    // only cares about not covering holes
+    ("careful", Weights { height: -0.2, lines: 0.3, holes: -1.0, bumpiness: -0.1 }),
+];
+
+impl Weights {
+    // either the name of a strategy or 4 weights separated by commas (height,
+    // lines, holes and bumpiness)
+    pub fn parse(text: &str) -> Option<Weights> {
+        if let Some((_, weights)) = STRATEGIES.iter().find(|(name, _)| *name == text) {
+            return Some(*weights);
+        }
+        let values = text.split(',')
+                         .map(|value| value.trim().parse::<f64>().ok())
+                         .collect::<Option<Vec<f64>>>()?;
+        if values.len() != 4 {
+            return None;
+        }
+        Some(Weights { height: values[0], lines: values[1], holes: values[2], bumpiness: values[3] })
+    }
+}
+
+// Plays by picking, for every new piece, the placement leaving the best
+// looking board, then giving one input per frame to get there.
+pub struct Bot {
+    weights: Weights,
+    // rotation state and column wanted for the current piece
+    target: Option<(u8, isize)>,
+    // position of the piece when the last input was given
+    last_position: Option<(isize, u8)>,
+    // `Tetris::spawned_pieces` when the target was chosen, the piece having
+    // possibly been locked by gravity before reaching it
+    piece_nb: u32,
+}
+
+impl Bot {
+    pub fn new() -> Bot {
+        Bot::with_weights(DEFAULT_WEIGHTS)
+    }
+
+    pub fn with_weights(weights: Weights) -> Bot {
+        Bot {
+            weights: weights,
+            target: None,
+            last_position: None,
+            piece_nb: 0,
+        }
+    }
+
+    pub fn next_input(&mut self, tetris: &Tetris) -> Option<Input> {
+        let piece = tetris.current_piece.as_ref()?;
+        let position = (piece.x, piece.current_state);
+        if tetris.spawned_pieces != self.piece_nb {
+            self.piece_nb = tetris.spawned_pieces;
+            self.target = None;
+            self.last_position = None;
+        }
+        let (state, x) = match self.target {
+            Some(target) => target,
+            None => {
+                let target = best_placement(&tetris.game_map, piece, &self.weights).unwrap_or((piece.current_state, piece.x));
+                self.target = Some(target);
+                target
+            },
+        };
+
+        // the last input didn't move the piece, it can't go any further
+        let blocked = self.last_position == Some(position);
+        self.last_position = Some(position);
+        let input = if blocked || (state == piece.current_state && x == piece.x) {
+            Input::HardDrop
+        } else if state != piece.current_state {
+            Input::Rotate
+        } else if x < piece.x {
+            Input::Left
+        } else {
+            Input::Right
+        };
+        Some(input)
+    }
+}
+
+impl Default for Bot {
+    fn default() -> Bot {
+        Bot::new()
+    }
+}
+
+fn place(board: &mut Board, piece: &Tetrimino, state: usize, x: isize, y: usize) {
+    for (line_nb, line) in piece.states[state].iter().enumerate() {
+        for (case_nb, case) in line.iter().enumerate() {
+            if *case != 0 {
+                board.set((x + case_nb as isize) as usize, y + line_nb, *case);
+            }
+        }
+    }
+}
+
+fn evaluate(board: &Board, nb_cleared: u32, weights: &Weights) -> f64 {
+    let mut heights = Vec::with_capacity(board.width());
+    let mut holes = 0;
+    for x in 0..board.width() {
+        let top = (0..board.len()).find(|y| board[*y][x] != 0).unwrap_or(board.len());
+        heights.push(board.len() - top);
+        holes += (top..board.len()).filter(|y| board[*y][x] == 0).count();
+    }
+    let aggregate_height: usize = heights.iter().sum();
+    let bumpiness: usize = heights.windows(2)
+                                  .map(|pair| pair[0].abs_diff(pair[1]))
+                                  .sum();
+    weights.height * aggregate_height as f64
+        + weights.lines * nb_cleared as f64
+        + weights.holes * holes as f64
+        + weights.bumpiness * bumpiness as f64
+}
+
+// best (state, x) to drop the piece at, from its current line
+pub fn best_placement(board: &Board, piece: &Tetrimino, weights: &Weights) -> Option<(u8, isize)> {
+    let mut best = None;
+    let mut best_score = 0.;
+    for state in 0..piece.states.len() {
+        for x in -3..board.width() as isize {
+            if !piece.test_position(board, state, x, piece.y) {
+                continue
+            }
+            let mut y = piece.y;
+            while piece.test_position(board, state, x, y + 1) {
+                y += 1;
+            }
+
+            let mut result = board.clone();
+            place(&mut result, piece, state, x, y);
+            let nb_cleared = result.clear_lines();
+            let score = evaluate(&result, nb_cleared, weights);
+            if best.is_none() || score > best_score {
+                best = Some((state as u8, x));
+                best_score = score;
+            }
+        }
+    }
+    best
+}
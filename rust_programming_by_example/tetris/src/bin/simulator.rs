@@ -0,0 +1,278 @@
+extern crate tetris;
+
+use tetris::ai::{Bot, Weights, STRATEGIES};
+use tetris::events::{simulate_frame, EventBus};
+use tetris::game_board::{GameConfig, Tetris};
+use tetris::stats::Stats;
+
+use std::cell::RefCell;
+use std::fs;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+const USAGE: &'static str = "Usage: simulator [OPTIONS]
+
+Plays seeded games with bots, the same seeds being used for every strategy.
+
+  --strategy S       bot strategy, can be given several times: a name
+                     (default, flat, careful) or 4 weights separated by
+                     commas (height,lines,holes,bumpiness)
+  --games N          number of games per strategy (default 100)
+  --seed N           seed of the first game (default 1)
+  --max-pieces N     stop a game after N pieces (default 10000)
+  --threads N        number of threads (default: number of CPUs)
+  --board WxH        board size in cases (default 10x16)
+  --level N          starting level (default 1)
+  --csv FILE         write the result of every game to FILE
+  --json FILE        write the results and the summaries to FILE
+  -h, --help         show this help";
+
+// reason of the games stopped before their end
+const PIECE_LIMIT: &'static str = "piece-limit";
+
+struct Strategy {
+    name: String,
+    weights: Weights,
+}
+
+struct GameResult {
+    strategy: usize,
+    game_nb: u32,
+    seed: u64,
+    score: u32,
+    lines: u32,
+    level: u32,
+    pieces: u32,
+    // why the game ended
+    reason: &'static str,
+}
+
+struct Summary {
+    nb_games: usize,
+    mean_score: f64,
+    mean_lines: f64,
+    median_lines: u32,
+    min_lines: u32,
+    max_lines: u32,
+    mean_pieces: f64,
+    // part of the games lost before reaching the piece limit
+    top_out_rate: f64,
+}
+
+struct Options {
+    strategies: Vec<Strategy>,
+    nb_games: u32,
+    seed: u64,
+    max_pieces: u32,
+    nb_threads: usize,
+    config: GameConfig,
+    csv: Option<String>,
+    json: Option<String>,
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("invalid value \"{}\" for {}", value, option))
+}
+
+// returns None when the help has been asked
+fn parse_options() -> Result<Option<Options>, String> {
+    let mut options = Options {
+        strategies: Vec::new(),
+        nb_games: 100,
+        seed: 1,
+        max_pieces: 10000,
+        nb_threads: thread::available_parallelism().map(|nb| nb.get()).unwrap_or(4),
+        config: GameConfig::new(),
+        csv: None,
+        json: None,
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(None);
+        }
+        let value = args.next().ok_or(format!("missing value for {}", arg))?;
+        match arg.as_str() {
+            "--strategy" => {
+                let weights = Weights::parse(&value).ok_or(format!("unknown strategy \"{}\"", value))?;
+                options.strategies.push(Strategy { name: value, weights: weights });
+            },
+            "--games" => options.nb_games = parse_number(&arg, &value)?,
+            "--seed" => options.seed = parse_number(&arg, &value)?,
+            "--max-pieces" => options.max_pieces = parse_number(&arg, &value)?,
+            "--threads" => options.nb_threads = parse_number::<usize>(&arg, &value)?.max(1),
+            "--board" => {
+                let mut parts = value.splitn(2, 'x');
+                options.config.nb_columns = parse_number(&arg, parts.next().unwrap_or(""))?;
+                options.config.nb_lines = parse_number(&arg, parts.next().unwrap_or(""))?;
+            },
+            "--level" => options.config.level = parse_number(&arg, &value)?,
+            "--csv" => options.csv = Some(value),
+            "--json" => options.json = Some(value),
+            _ => return Err(format!("unknown option \"{}\"", arg)),
+        }
+    }
+    options.config.check()?;
+    if options.strategies.is_empty() {
+        let (name, weights) = STRATEGIES[0];
+        options.strategies.push(Strategy { name: name.to_owned(), weights: weights });
+    }
+    Ok(Some(options))
+}
+
+fn play_game(config: &GameConfig, weights: Weights, max_pieces: u32) -> (Tetris, u32) {
+    let mut tetris = Tetris::from_config(config);
+    let mut bot = Bot::with_weights(weights);
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let mut bus = EventBus::new();
+    bus.subscribe(&stats);
+    while !tetris.is_game_over() && stats.borrow().nb_pieces < max_pieces {
+        let inputs = bot.next_input(&tetris).into_iter().collect::<Vec<_>>();
+        simulate_frame(&mut tetris, &inputs, Some(&stats), None, &bus);
+    }
+    let nb_pieces = stats.borrow().nb_pieces;
+    (tetris, nb_pieces)
+}
+
+// plays every (strategy, game) pair, spread over the threads
+fn run_games(options: Arc<Options>) -> Vec<GameResult> {
+    let nb_jobs = options.strategies.len() * options.nb_games as usize;
+    let next_job = Arc::new(AtomicUsize::new(0));
+    let results = Arc::new(Mutex::new(Vec::with_capacity(nb_jobs)));
+
+    let threads = (0..options.nb_threads.min(nb_jobs.max(1)))
+        .map(|_| {
+            let options = options.clone();
+            let next_job = next_job.clone();
+            let results = results.clone();
+            thread::spawn(move || {
+                loop {
+                    let job = next_job.fetch_add(1, Ordering::SeqCst);
+                    if job >= nb_jobs {
+                        break
+                    }
+                    let strategy = job / options.nb_games as usize;
+                    let game_nb = (job % options.nb_games as usize) as u32;
+                    let mut config = options.config.clone();
+                    config.seed = options.seed.wrapping_add(game_nb as u64);
+                    let (tetris, nb_pieces) = play_game(&config, options.strategies[strategy].weights,
+                                                        options.max_pieces);
+                    let result = GameResult {
+                        strategy: strategy,
+                        game_nb: game_nb,
+                        seed: config.seed,
+                        score: tetris.score,
+                        lines: tetris.nb_lines,
+                        level: tetris.current_level,
+                        pieces: nb_pieces,
+                        reason: tetris.game_over.map_or(PIECE_LIMIT, |reason| reason.name()),
+                    };
+                    results.lock().expect("Couldn't lock the results").push(result);
+                }
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().expect("A simulation thread panicked");
+    }
+
+    let mut results = Arc::try_unwrap(results)
+        .ok()
+        .expect("The results are still shared")
+        .into_inner()
+        .expect("Couldn't get the results");
+    results.sort_by_key(|result| (result.strategy, result.game_nb));
+    results
+}
+
+fn summarize(results: &[&GameResult]) -> Summary {
+    let nb_games = results.len().max(1) as f64;
+    let mut lines = results.iter().map(|result| result.lines).collect::<Vec<_>>();
+    lines.sort();
+    Summary {
+        nb_games: results.len(),
+        mean_score: results.iter().map(|result| result.score as f64).sum::<f64>() / nb_games,
+        mean_lines: results.iter().map(|result| result.lines as f64).sum::<f64>() / nb_games,
+        median_lines: lines.get(lines.len() / 2).cloned().unwrap_or(0),
+        min_lines: lines.first().cloned().unwrap_or(0),
+        max_lines: lines.last().cloned().unwrap_or(0),
+        mean_pieces: results.iter().map(|result| result.pieces as f64).sum::<f64>() / nb_games,
+        top_out_rate: results.iter().filter(|result| result.reason != PIECE_LIMIT).count() as f64 / nb_games,
+    }
+}
+
+fn to_csv(options: &Options, results: &[GameResult]) -> String {
+    let mut content = "strategy,game,seed,score,lines,level,pieces,reason\n".to_owned();
+    for result in results {
+        content += &format!("\"{}\",{},{},{},{},{},{},{}\n",
+                            options.strategies[result.strategy].name, result.game_nb + 1, result.seed,
+                            result.score, result.lines, result.level, result.pieces, result.reason);
+    }
+    content
+}
+
+fn json_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn to_json(options: &Options, results: &[GameResult], summaries: &[Summary]) -> String {
+    let games = results.iter()
+        .map(|result| format!("    {{\"strategy\": {}, \"game\": {}, \"seed\": {}, \"score\": {}, \"lines\": {}, \
+                               \"level\": {}, \"pieces\": {}, \"reason\": {}}}",
+                              json_string(&options.strategies[result.strategy].name), result.game_nb + 1,
+                              result.seed, result.score, result.lines, result.level, result.pieces,
+                              json_string(result.reason)))
+        .collect::<Vec<_>>();
+    let summaries = options.strategies.iter()
+        .zip(summaries.iter())
+        .map(|(strategy, summary)| format!("    {{\"strategy\": {}, \"games\": {}, \"mean_score\": {:.2}, \
+                                             \"mean_lines\": {:.2}, \"median_lines\": {}, \"min_lines\": {}, \
+                                             \"max_lines\": {}, \"mean_pieces\": {:.2}, \"top_out_rate\": {:.4}}}",
+                                            json_string(&strategy.name), summary.nb_games, summary.mean_score,
+                                            summary.mean_lines, summary.median_lines, summary.min_lines,
+                                            summary.max_lines, summary.mean_pieces, summary.top_out_rate))
+        .collect::<Vec<_>>();
+    format!("{{\n  \"games\": [\n{}\n  ],\n  \"summaries\": [\n{}\n  ]\n}}\n",
+            games.join(",\n"), summaries.join(",\n"))
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(Some(options)) => Arc::new(options),
+        Ok(None) => {
+            println!("{}", USAGE);
+            return
+        },
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        },
+    };
+
+    let results = run_games(options.clone());
+    let summaries = (0..options.strategies.len())
+        .map(|strategy| summarize(&results.iter().filter(|result| result.strategy == strategy).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+
+    println!("{:<20} {:>6} {:>12} {:>10} {:>8} {:>10} {:>8}",
+             "strategy", "games", "mean score", "mean lines", "median", "min-max", "top out");
+    for (strategy, summary) in options.strategies.iter().zip(summaries.iter()) {
+        println!("{:<20} {:>6} {:>12.1} {:>10.1} {:>8} {:>10} {:>7.1}%",
+                 strategy.name, summary.nb_games, summary.mean_score, summary.mean_lines, summary.median_lines,
+                 format!("{}-{}", summary.min_lines, summary.max_lines), summary.top_out_rate * 100.);
+    }
+
+    if let Some(ref path) = options.csv {
+        if let Err(e) = fs::write(path, to_csv(&options, &results)) {
+            eprintln!("Couldn't write {}: {}", path, e);
+        }
+    }
+    if let Some(ref path) = options.json {
+        if let Err(e) = fs::write(path, to_json(&options, &results, &summaries)) {
+            eprintln!("Couldn't write {}: {}", path, e);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+extern crate tetris;
+
+use tetris::replay::{Checkpoint, Replay};
+
+use std::process;
+
+const USAGE: &'static str = "Usage: verify REPLAY [--score N] [--lines N]
+
+Plays the replay back without a window and tells if the claimed result is the
+one the game really ends with. The checkpoints of the replay, if any, are
+checked too and show where the game first went differently. Without a claim,
+only the checkpoints are checked. Finesse training replays aren't accepted, as
+such a game never ends.
+
+  --score N          claimed final score
+  --lines N          claimed number of lines
+  -h, --help         show this help
+
+Exits with 0 if the claim is valid, 1 if it isn't or if there is nothing to
+check it against and 2 on errors.";
+
+struct Options {
+    path: String,
+    score: Option<u32>,
+    nb_lines: Option<u32>,
+}
+
+fn parse_number(option: &str, value: Option<String>) -> Result<u32, String> {
+    let value = value.ok_or(format!("missing value for {}", option))?;
+    value.parse::<u32>().map_err(|_| format!("invalid value \"{}\" for {}", value, option))
+}
+
+// returns None when the help has been asked
+fn parse_options() -> Result<Option<Options>, String> {
+    let mut path = None;
+    let mut score = None;
+    let mut nb_lines = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--score" => score = Some(parse_number(&arg, args.next())?),
+            "--lines" => nb_lines = Some(parse_number(&arg, args.next())?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option \"{}\"", arg)),
+            _ if path.is_none() => path = Some(arg),
+            _ => return Err(format!("unexpected argument \"{}\"", arg)),
+        }
+    }
+    let path = path.ok_or("missing replay file")?;
+    Ok(Some(Options { path: path, score: score, nb_lines: nb_lines }))
+}
+
+fn describe(checkpoint: &Option<Checkpoint>) -> String {
+    match *checkpoint {
+        Some(ref checkpoint) => format!("piece locked at frame {}, score {}, {} lines, board {:016x}",
+                                        checkpoint.frame, checkpoint.score, checkpoint.nb_lines, checkpoint.board),
+        None => "no piece locked".to_owned(),
+    }
+}
+
+fn main() {
+    let options = match parse_options() {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return
+        },
+        Err(e) => {
+            println!("{}", e);
+            println!("{}", USAGE);
+            process::exit(2);
+        },
+    };
+    let replay = match Replay::load(&options.path) {
+        Ok(replay) => replay,
+        Err(e) => {
+            println!("Couldn't load the replay: {}", e);
+            process::exit(2);
+        },
+    };
+    if replay.config.trainer {
+        println!("Finesse training replays can't be verified");
+        process::exit(2);
+    }
+    if options.score.is_none() && options.nb_lines.is_none() && replay.checkpoints.is_empty() {
+        println!("Unverified: the replay has no checkpoints, give the claimed --score or --lines");
+        process::exit(1);
+    }
+
+    let verification = replay.verify();
+    println!("True result: score {}, {} lines, level {}, {} frames{}",
+             verification.score, verification.nb_lines, verification.level, verification.nb_frames,
+             verification.game_over.map(|reason| format!(", {}", reason.description().to_lowercase())).unwrap_or_default());
+    if replay.checkpoints.is_empty() {
+        println!("The replay has no checkpoints");
+    }
+    if let Some(ref divergence) = verification.divergence {
+        println!("Diverged at frame {}:", divergence.frame);
+        println!("  recorded:  {}", describe(&divergence.recorded));
+        println!("  simulated: {}", describe(&divergence.simulated));
+    }
+
+    if verification.matches(options.score, options.nb_lines) {
+        println!("Valid");
+    } else {
+        if options.score.is_some_and(|score| score != verification.score) ||
+           options.nb_lines.is_some_and(|nb_lines| nb_lines != verification.nb_lines) {
+            println!("Claimed: score {}, {} lines",
+                     options.score.map(|score| score.to_string()).unwrap_or("-".to_owned()),
+                     options.nb_lines.map(|nb_lines| nb_lines.to_string()).unwrap_or("-".to_owned()));
+        }
+        println!("Invalid");
+        process::exit(1);
+    }
+}
@@ -0,0 +1,190 @@
+use std::ops::Index;
+use std::slice;
+
+// one bit per column in the line masks
+pub const MAX_WIDTH: usize = 16;
+
+// The game map: one bit mask per line, used for the collision tests and the
+// line clears, along with the value of every case (0 for an empty case, the
+// piece kind + 1 otherwise) for the display.
+#[derive(Clone, PartialEq)]
+pub struct Board {
+    lines: Vec<u16>,
+    cases: Vec<Vec<u8>>,
+    full_line: u16,
+}
+
+impl Board {
+    pub fn new(width: usize, height: usize) -> Board {
+        assert!(width > 0 && width <= MAX_WIDTH, "invalid board width {}", width);
+        Board {
+            lines: vec![0; height],
+            cases: vec![vec![0; width]; height],
+            full_line: ((1u32 << width) - 1) as u16,
+        }
+    }
+
+    pub fn from_lines(lines: Vec<Vec<u8>>) -> Result<Board, String> {
+        let width = lines.first().map(|line| line.len()).unwrap_or(0);
+        if width == 0 || width > MAX_WIDTH || lines.iter().any(|line| line.len() != width) {
+            return Err("invalid map".to_owned());
+        }
+        let mut board = Board::new(width, lines.len());
+        for (y, line) in lines.iter().enumerate() {
+            for (x, case) in line.iter().enumerate() {
+                board.set(x, y, *case);
+            }
+        }
+        Ok(board)
+    }
+
+    pub fn width(&self) -> usize {
+        self.cases[0].len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, Vec<u8>> {
+        self.cases.iter()
+    }
+
+    // the same board with `nb_lines` empty lines added on top
+    pub fn with_lines_above(&self, nb_lines: usize) -> Board {
+        let mut board = Board::new(self.width(), self.len() + nb_lines);
+        board.lines[nb_lines..].copy_from_slice(&self.lines);
+        board.cases[nb_lines..].clone_from_slice(&self.cases);
+        board
+    }
+
+    // the same board without its `nb_lines` top lines
+    pub fn without_lines_above(&self, nb_lines: usize) -> Board {
+        Board {
+            lines: self.lines[nb_lines..].to_vec(),
+            cases: self.cases[nb_lines..].to_vec(),
+            full_line: self.full_line,
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, case: u8) {
+        self.cases[y][x] = case;
+        if case != 0 {
+            self.lines[y] |= 1 << x;
+        } else {
+            self.lines[y] &= !(1 << x);
+        }
+    }
+
+    // cases outside of the board count as occupied
+    pub fn is_occupied(&self, x: isize, y: isize) -> bool {
+        if x < 0 || y < 0 || x as usize >= self.width() || y as usize >= self.len() {
+            return true;
+        }
+        self.lines[y as usize] & (1 << x) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(|line| *line == 0)
+    }
+
+    // FNV-1a hash of the occupied cases, to compare boards without keeping them
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash = 0xcbf29ce484222325u64;
+        for line in self.lines.iter() {
+            for byte in line.to_le_bytes().iter() {
+                hash ^= *byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        }
+        hash
+    }
+
+    // Tells if a piece fits with its top left corner at (x, y), `mask` being
+    // the bit masks of its 4 lines (bit 0 for the leftmost column).
+    pub fn fits(&self, mask: &[u16; 4], x: isize, y: usize) -> bool {
+        for (decal_y, piece_line) in mask.iter().enumerate() {
+            if *piece_line == 0 {
+                continue
+            }
+            let shifted = if x < 0 {
+                let shift = (-x) as u32;
+                // the columns going out on the left must be empty
+                if shift >= 4 || piece_line & ((1 << shift) - 1) != 0 {
+                    return false;
+                }
+                piece_line >> shift
+            } else {
+                if x as usize >= MAX_WIDTH {
+                    return false;
+                }
+                let shifted = (*piece_line as u32) << x;
+                if shifted & !(self.full_line as u32) != 0 {
+                    return false;
+                }
+                shifted as u16
+            };
+            match self.lines.get(y + decal_y) {
+                Some(line) if line & shifted == 0 => {},
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    // number of lines the piece can fall from (x, y)
+    pub fn drop_distance(&self, mask: &[u16; 4], x: isize, y: usize) -> usize {
+        let mut distance = 0;
+        while self.fits(mask, x, y + distance + 1) {
+            distance += 1;
+        }
+        distance
+    }
+
+    // Adds a line at the bottom, moving the others up the way `clear_lines`
+    // moves them down. Returns false if the top line wasn't empty, its blocks
+    // being lost.
+    pub fn push_line(&mut self, cases: &[u8]) -> bool {
+        let top_was_empty = self.lines[0] == 0;
+        let height = self.len();
+        for y in 1..height {
+            self.lines[y - 1] = self.lines[y];
+            self.cases.swap(y - 1, y);
+        }
+        self.lines[height - 1] = 0;
+        for (x, case) in cases.iter().enumerate() {
+            self.set(x, height - 1, *case);
+        }
+        top_was_empty
+    }
+
+    // Removes the full lines, moving the ones above down. Returns the number
+    // of removed lines.
+    pub fn clear_lines(&mut self) -> u32 {
+        let height = self.len();
+        // next line to fill, going up
+        let mut kept = height;
+        for y in (0..height).rev() {
+            if self.lines[y] != self.full_line {
+                kept -= 1;
+                self.lines[kept] = self.lines[y];
+                self.cases.swap(kept, y);
+            }
+        }
+        for y in 0..kept {
+            self.lines[y] = 0;
+            for case in self.cases[y].iter_mut() {
+                *case = 0;
+            }
+        }
+        kept as u32
+    }
+}
+
+impl Index<usize> for Board {
+    type Output = [u8];
+
+    fn index(&self, y: usize) -> &[u8] {
+        &self.cases[y]
+    }
+}
@@ -0,0 +1,266 @@
+use crate::game_board::{GameConfig, FRAMES_PER_SECOND};
+use crate::modifiers::Modifiers;
+
+const USAGE: &'static str = "Usage: tetris [OPTIONS]
+
+Game:
+  --mode MODE          start directly in a mode: marathon, practice, sprint,
+                       ultra, puzzles, dig, trainer or daily
+  --level N            starting level (1 to 10)
+  --seed N             seed of the piece generator
+  --board WxH          board size in cases (default 10x16)
+  --entry-delay N      frames before a new piece appears (0 to 60, default 0)
+  --modifiers LIST     modifiers of the games, separated by commas: big, mirror,
+                       invisible, monochrome, no-rotation
+  --irs on|off         apply the rotation and hold keys pressed before a piece
+                       appears to it (default on)
+  --highscores FILE    highscores file (default scores.txt)
+  --daily-scores FILE  results of the daily challenges, which can be shared by
+                       a team (default daily_scores.txt); the daily challenge
+                       ignores the other game options
+  --dig-lines N        garbage lines to clear in the dig race (default 10)
+  --garbage-rise N     seconds between rising garbage lines in the dig race
+                       (default 0: no rising garbage)
+  --position POS       start the marathon games from a position: a file in the
+                       text format or a fumen string
+  --convert POS        print the position in the text and fumen formats
+
+Display:
+  --window WxH         window size in pixels (default 800x800)
+  --fullscreen         start in fullscreen
+  --theme NAME         theme to use instead of the one from the settings
+
+Replays and bots:
+  --record FILE        save the replay of the marathon game to FILE
+  --replay FILE        play back the replay from FILE
+  --ai                 let the bot play the marathon games
+  --headless           run without a window (needs --ai or --replay)
+  --log-events         print the events of the marathon games and the replays
+                       played headless on stderr as they happen
+  --games N            number of games played by the bot headless (default 1)
+  --max-pieces N       stop the headless bot games after N pieces (default 10000)
+  --export PATH        render the replay without a window: into an animated GIF
+                       if PATH ends with .gif, as PNG frames in the PATH
+                       directory otherwise (the size is the --window one)
+  --fps N              frame rate of the export (default 20)
+
+  -h, --help           show this help";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Marathon,
+    // marathon in which the placements can be taken back
+    Practice,
+    // 40 lines as fast as possible
+    Sprint,
+    // as many points as possible in 3 minutes
+    Ultra,
+    Puzzles,
+    Dig,
+    Trainer,
+    // marathon with the seed and modifiers of the day
+    Daily,
+}
+
+pub struct Options {
+    pub mode: Option<Mode>,
+    // the seed is only kept from here when given
+    pub config: GameConfig,
+    pub seed: Option<u64>,
+    pub dig_lines: usize,
+    // in frames
+    pub garbage_interval: u32,
+    pub highscore_file: String,
+    pub daily_score_file: String,
+    pub window_size: (u32, u32),
+    pub fullscreen: bool,
+    pub theme: Option<String>,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub ai: bool,
+    pub headless: bool,
+    pub log_events: bool,
+    pub nb_games: u32,
+    pub max_pieces: u32,
+    pub export: Option<String>,
+    pub export_fps: u32,
+    pub position: Option<String>,
+    pub convert: Option<String>,
+    pub help: bool,
+}
+
+pub fn print_usage() {
+    println!("{}", USAGE);
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| format!("invalid value \"{}\" for {}", value, option))
+}
+
+fn parse_size(option: &str, value: &str) -> Result<(u32, u32), String> {
+    let mut parts = value.splitn(2, 'x');
+    let width = parse_number(option, parts.next().unwrap_or(""))?;
+    let height = parse_number(option, parts.next().unwrap_or(""))?;
+    Ok((width, height))
+}
+
+impl Options {
+    // `args` doesn't include the program name
+    pub fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<Options, String> {
+        let mut options = Options {
+            mode: None,
+            config: GameConfig::new(),
+            seed: None,
+            dig_lines: 10,
+            garbage_interval: 0,
+            highscore_file: crate::HIGHSCORE_FILE.to_owned(),
+            daily_score_file: crate::DAILY_SCORE_FILE.to_owned(),
+            window_size: (800, 800),
+            fullscreen: false,
+            theme: None,
+            record: None,
+            replay: None,
+            ai: false,
+            headless: false,
+            log_events: false,
+            nb_games: 1,
+            max_pieces: 10000,
+            export: None,
+            export_fps: 20,
+            position: None,
+            convert: None,
+            help: false,
+        };
+
+        while let Some(arg) = args.next() {
+            // flags
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    options.help = true;
+                    continue
+                },
+                "--fullscreen" => {
+                    options.fullscreen = true;
+                    continue
+                },
+                "--ai" => {
+                    options.ai = true;
+                    continue
+                },
+                "--headless" => {
+                    options.headless = true;
+                    continue
+                },
+                "--log-events" => {
+                    options.log_events = true;
+                    continue
+                },
+                _ => {},
+            }
+
+            // options with a value
+            let value = match args.next() {
+                Some(value) => value,
+                None if arg.starts_with('-') => return Err(format!("missing value for {}", arg)),
+                None => return Err(format!("unexpected argument \"{}\"", arg)),
+            };
+            match arg.as_str() {
+                "--mode" => {
+                    options.mode = Some(match value.as_str() {
+                        "marathon" => Mode::Marathon,
+                        "practice" => Mode::Practice,
+                        "sprint" => Mode::Sprint,
+                        "ultra" => Mode::Ultra,
+                        "puzzles" => Mode::Puzzles,
+                        "dig" => Mode::Dig,
+                        "trainer" => Mode::Trainer,
+                        "daily" => Mode::Daily,
+                        _ => return Err(format!("unknown mode \"{}\"", value)),
+                    });
+                },
+                "--level" => options.config.level = parse_number(&arg, &value)?,
+                "--seed" => options.seed = Some(parse_number(&arg, &value)?),
+                "--board" => {
+                    let (width, height) = parse_size(&arg, &value)?;
+                    options.config.nb_columns = width as usize;
+                    options.config.nb_lines = height as usize;
+                },
+                "--entry-delay" => options.config.entry_delay = parse_number(&arg, &value)?,
+                "--irs" => {
+                    options.config.initial_actions = match value.as_str() {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(format!("invalid value \"{}\" for {}", value, arg)),
+                    };
+                },
+                "--modifiers" => options.config.modifiers = Modifiers::parse(&value)?,
+                "--dig-lines" => options.dig_lines = parse_number(&arg, &value)?,
+                "--garbage-rise" => options.garbage_interval = parse_number::<u32>(&arg, &value)?.saturating_mul(FRAMES_PER_SECOND),
+                "--highscores" => options.highscore_file = value,
+                "--daily-scores" => options.daily_score_file = value,
+                "--window" => options.window_size = parse_size(&arg, &value)?,
+                "--theme" => options.theme = Some(value),
+                "--record" => options.record = Some(value),
+                "--replay" => options.replay = Some(value),
+                "--games" => options.nb_games = parse_number(&arg, &value)?,
+                "--max-pieces" => options.max_pieces = parse_number(&arg, &value)?,
+                "--export" => options.export = Some(value),
+                "--fps" => options.export_fps = parse_number(&arg, &value)?,
+                "--position" => options.position = Some(value),
+                "--convert" => options.convert = Some(value),
+                _ => return Err(format!("unknown option \"{}\"", arg)),
+            }
+        }
+
+        options.config.check()?;
+        if options.headless && !options.ai && options.replay.is_none() {
+            return Err("--headless needs --ai or --replay".to_owned());
+        }
+        if options.replay.is_some() && (options.ai || options.record.is_some()) {
+            return Err("--replay can't be used with --ai or --record".to_owned());
+        }
+        if options.export.is_some() && options.replay.is_none() {
+            return Err("--export needs --replay".to_owned());
+        }
+        if options.record.is_some() && options.headless && options.nb_games != 1 {
+            return Err("--record only saves a single game".to_owned());
+        }
+        if options.mode == Some(Mode::Dig) {
+            options.dig_config(0).check()?;
+        }
+        if matches!(options.mode, Some(Mode::Practice) | Some(Mode::Sprint) | Some(Mode::Ultra) | Some(Mode::Puzzles) |
+                                  Some(Mode::Dig) | Some(Mode::Trainer) | Some(Mode::Daily)) && (options.ai || options.record.is_some()) {
+            return Err("--ai and --record only apply to marathon games".to_owned());
+        }
+        if options.position.is_some() && (options.record.is_some() || options.replay.is_some()) {
+            return Err("replays always start from an empty board, --position can't be used with them".to_owned());
+        }
+        Ok(options)
+    }
+
+    // configuration of the `game_nb`-th game, with a new seed unless one was
+    // given (in which case the games follow each other from there)
+    pub fn game_config(&self, game_nb: u32) -> GameConfig {
+        let mut config = self.config.clone();
+        config.seed = match self.seed {
+            Some(seed) => seed.wrapping_add(game_nb as u64),
+            None => GameConfig::new().seed,
+        };
+        config
+    }
+
+    // configuration of a dig race, the `game_nb`-th game played
+    pub fn dig_config(&self, game_nb: u32) -> GameConfig {
+        let mut config = self.game_config(game_nb);
+        config.garbage_lines = self.dig_lines;
+        config.garbage_interval = self.garbage_interval;
+        config
+    }
+
+    // configuration of a finesse training session, the `game_nb`-th game played
+    pub fn trainer_config(&self, game_nb: u32) -> GameConfig {
+        let mut config = self.game_config(game_nb);
+        config.trainer = true;
+        config
+    }
+}
@@ -0,0 +1,161 @@
+use crate::game_board::GameConfig;
+use crate::modifiers::{Modifiers, MODIFIER_NAMES};
+use crate::profiles::format_date;
+
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// mixed into the day number for the seed of the challenge
+const DAILY_SEED: u64 = 0x5d1c_07a3_e94b_62f8;
+// one day out of this many gets a modifier
+const MODIFIER_DAYS: u64 = 3;
+const LEADERBOARD_SIZE: usize = 10;
+
+// splitmix64, so that following days get unrelated seeds
+fn mix(value: u64) -> u64 {
+    let mut z = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// The game of the day, the same for everyone playing it on that (UTC) date.
+pub struct Challenge {
+    // YYYY-MM-DD
+    pub date: String,
+    pub seed: u64,
+    pub modifiers: Modifiers,
+}
+
+impl Challenge {
+    // `day` is counted from the epoch
+    pub fn for_day(day: u64) -> Challenge {
+        let seed = mix(day ^ DAILY_SEED);
+        let mut modifiers = Modifiers::none();
+        let picked = mix(seed);
+        if picked % MODIFIER_DAYS == 0 {
+            modifiers.toggle(MODIFIER_NAMES[(picked / MODIFIER_DAYS) as usize % MODIFIER_NAMES.len()]);
+        }
+        Challenge {
+            date: format_date(day * 86400),
+            seed: seed,
+            modifiers: modifiers,
+        }
+    }
+
+    pub fn today() -> Challenge {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+        Challenge::for_day(now / 86400)
+    }
+
+    // a marathon on the default board, whatever the command line says
+    pub fn config(&self) -> GameConfig {
+        let mut config = GameConfig::new();
+        config.seed = self.seed;
+        config.modifiers = self.modifiers;
+        config
+    }
+}
+
+// best result of a player on a challenge
+struct Entry {
+    date: String,
+    score: u32,
+    lines: u32,
+    attempts: u32,
+    player: String,
+}
+
+// The results of the daily challenges, the file being shareable between the
+// players of a team.
+pub struct DailyScores {
+    entries: Vec<Entry>,
+}
+
+impl DailyScores {
+    // One line per player and date, the name coming last as it can contain
+    // spaces:
+    //
+    // 2026-10-18 1250 34 2 alice
+    pub fn parse(content: &str) -> DailyScores {
+        let entries = content.lines()
+                             .filter_map(|line| {
+                                 let mut parts = line.splitn(5, ' ');
+                                 Some(Entry {
+                                     date: parts.next()?.to_owned(),
+                                     score: parts.next()?.parse().ok()?,
+                                     lines: parts.next()?.parse().ok()?,
+                                     attempts: parts.next()?.parse().ok()?,
+                                     player: parts.next()?.to_owned(),
+                                 })
+                             })
+                             .collect();
+        DailyScores { entries: entries }
+    }
+
+    // an unreadable file gives no results
+    pub fn load(file_name: &str) -> DailyScores {
+        DailyScores::parse(&fs::read_to_string(file_name).unwrap_or_default())
+    }
+
+    pub fn to_text(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| format!("{} {} {} {} {}\n", entry.date, entry.score, entry.lines, entry.attempts, entry.player))
+            .collect()
+    }
+
+    pub fn save(&self, file_name: &str) -> bool {
+        fs::write(file_name, self.to_text()).is_ok()
+    }
+
+    pub fn has_played(&self, date: &str, player: &str) -> bool {
+        self.entries.iter().any(|entry| entry.date == date && entry.player == player)
+    }
+
+    // results of a date, best score first
+    fn ranking(&self, date: &str) -> Vec<&Entry> {
+        let mut ranking = self.entries.iter().filter(|entry| entry.date == date).collect::<Vec<_>>();
+        ranking.sort_by(|a, b| b.score.cmp(&a.score).then(b.lines.cmp(&a.lines)));
+        ranking
+    }
+
+    // Adds an attempt of a player, only the best one being kept. Returns the
+    // rank of the player on that date, from 1.
+    pub fn add(&mut self, date: &str, player: &str, score: u32, lines: u32) -> usize {
+        match self.entries.iter_mut().find(|entry| entry.date == date && entry.player == player) {
+            Some(entry) => {
+                entry.attempts += 1;
+                if (score, lines) > (entry.score, entry.lines) {
+                    entry.score = score;
+                    entry.lines = lines;
+                }
+            },
+            None => {
+                self.entries.push(Entry {
+                    date: date.to_owned(),
+                    score: score,
+                    lines: lines,
+                    attempts: 1,
+                    player: player.to_owned(),
+                });
+            },
+        }
+        self.ranking(date).iter().position(|entry| entry.player == player).map_or(0, |index| index + 1)
+    }
+
+    pub fn leaderboard_lines(&self, date: &str) -> Vec<String> {
+        let ranking = self.ranking(date);
+        if ranking.is_empty() {
+            return vec!["Nobody played yet".to_owned()]
+        }
+        ranking.iter()
+               .take(LEADERBOARD_SIZE)
+               .enumerate()
+               .map(|(index, entry)| {
+                   format!("{}. {}: {} ({} lines, {} {})", index + 1, entry.player, entry.score, entry.lines,
+                           entry.attempts, if entry.attempts > 1 { "attempts" } else { "attempt" })
+               })
+               .collect()
+    }
+}
@@ -0,0 +1,264 @@
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, LCTRLMOD, RCTRLMOD};
+use sdl2::mouse::MouseButton;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use sdl2::EventPump;
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::achievements::AchievementTracker;
+use crate::board::Board;
+use crate::game_board::Tetris;
+use crate::history::History;
+use crate::layout::{handle_window_event, Layout};
+use crate::modifiers::Modifiers;
+use crate::position::Position;
+use crate::profiles::Profile;
+use crate::puzzle::{Goal, Puzzle};
+use crate::stats::Stats;
+use crate::theme::Theme;
+use crate::tetrimino::{kind_from_char, PIECE_NAMES};
+use crate::{create_texture_from_text, draw_board, game_bus, get_rect_from_text, run_game, GameEnd, Player};
+
+const HELP_LINES: [&'static str; 10] = ["Left click: paint",
+                                       "Right click: erase",
+                                       "1-7: color",
+                                       "IJLOSZT: add a piece",
+                                       "Backspace: remove a piece",
+                                       "Ctrl+Z: undo",
+                                       "Ctrl+C/V: copy/paste",
+                                       "G: change the goal",
+                                       "F2: save as a puzzle",
+                                       "Return: play"];
+
+// Board and pieces being edited, with what's needed to undo the changes.
+struct Sandbox {
+    game_map: Board,
+    pieces: Vec<u8>,
+    goal: Goal,
+    // painted with the left button, as stored in the map
+    color: u8,
+    history: Vec<(Board, Vec<u8>)>,
+    // case value set by the current drag, if a button is pressed
+    painting: Option<u8>,
+}
+
+impl Sandbox {
+    fn new() -> Sandbox {
+        Sandbox {
+            game_map: Tetris::new().visible_map(),
+            pieces: Vec::new(),
+            goal: Goal::ClearLines(1),
+            color: 1,
+            history: Vec::new(),
+            painting: None,
+        }
+    }
+
+    fn save_state(&mut self) {
+        self.history.push((self.game_map.clone(), self.pieces.clone()));
+    }
+
+    fn undo(&mut self) {
+        if let Some((game_map, pieces)) = self.history.pop() {
+            self.game_map = game_map;
+            self.pieces = pieces;
+        }
+    }
+
+    fn paint(&mut self, layout: &Layout, x: i32, y: i32) {
+        if let (Some(value), Some((case_x, case_y))) = (self.painting, layout.case_at(x, y)) {
+            self.game_map.set(case_x, case_y, value);
+        }
+    }
+
+    fn start_painting(&mut self, layout: &Layout, button: MouseButton, x: i32, y: i32) {
+        let value = match button {
+            MouseButton::Left => self.color,
+            MouseButton::Right => 0,
+            _ => return,
+        };
+        self.save_state();
+        self.painting = Some(value);
+        self.paint(layout, x, y);
+    }
+
+    fn stop_painting(&mut self) {
+        if self.painting.take().is_none() {
+            return
+        }
+        // nothing changed, no need to undo it
+        if self.history.last().is_some_and(|(game_map, _)| *game_map == self.game_map) {
+            self.history.pop();
+        }
+    }
+
+    fn create_game(&self) -> Tetris {
+        let mut tetris = Tetris::new();
+        tetris.set_visible_map(&self.game_map);
+        // the chosen pieces come first, then random ones
+        let mut queue = self.pieces.iter().cloned().collect::<VecDeque<_>>();
+        queue.extend(tetris.queue.drain(..));
+        tetris.queue = queue;
+        tetris
+    }
+
+    fn to_position(&self) -> Position {
+        Position {
+            game_map: self.game_map.clone(),
+            current: None,
+            hold: None,
+            queue: self.pieces.clone(),
+        }
+    }
+
+    // the current piece of the position, if any, comes first
+    fn load_position(&mut self, position: &Position) -> Result<(), String> {
+        let game_map = position.board(self.game_map.width(), self.game_map.len())?;
+        self.save_state();
+        self.game_map = game_map;
+        self.pieces = position.current.iter().chain(position.queue.iter()).cloned().collect();
+        Ok(())
+    }
+
+    fn to_puzzle(&self) -> Puzzle {
+        Puzzle {
+            name: "Sandbox".to_owned(),
+            goal: self.goal,
+            game_map: self.game_map.clone(),
+            pieces: self.pieces.clone(),
+        }
+    }
+}
+
+fn draw_sandbox(sandbox: &Sandbox,
+                canvas: &mut Canvas<Window>,
+                texture_creator: &TextureCreator<WindowContext>,
+                theme: &Theme,
+                layout: &Layout) {
+    draw_board(canvas, theme, layout, &sandbox.game_map, &Modifiers::none());
+
+    let pieces = sandbox.pieces.iter().map(|kind| PIECE_NAMES[*kind as usize]).collect::<String>();
+    let lines = [(format!("Color: {}", PIECE_NAMES[sandbox.color as usize - 1]), theme.highlight_color),
+                 (format!("Goal: {}", sandbox.goal.description()), theme.text_color),
+                 (format!("Pieces: {}", if pieces.is_empty() { "random" } else { &pieces }), theme.text_color)];
+    let help = HELP_LINES.iter().map(|line| (line.to_string(), theme.text_color));
+    for (line_nb, (text, color)) in lines.iter().cloned().chain(help).enumerate() {
+        let texture = create_texture_from_text(texture_creator, &theme.font, &text, color)
+            .expect("Couldn't render the sandbox text");
+        canvas.copy(&texture, None, get_rect_from_text(&theme.font, &text, layout.hud_x,
+                                                       layout.hud_line_y(line_nb as u32 + if line_nb < 3 { 0 } else { 1 }),
+                                                       layout.text_height))
+            .expect("Couldn't copy the sandbox text");
+    }
+    canvas.present();
+}
+
+// Lets the player draw a board and choose the next pieces, then play from
+// there as many times as wanted. Returns false if the window has been closed.
+pub fn run_sandbox(profile: &mut Profile,
+                   canvas: &mut Canvas<Window>,
+                   event_pump: &mut EventPump,
+                   texture_creator: &TextureCreator<WindowContext>,
+                   theme: &Theme) -> bool {
+    let mut sandbox = Sandbox::new();
+    let mut layout = Layout::from_canvas(canvas, sandbox.game_map.width(), sandbox.game_map.len());
+
+    loop {
+        let mut play = false;
+        for event in event_pump.poll_iter() {
+            if handle_window_event(&event, canvas) {
+                layout.update(canvas);
+                continue
+            }
+            match event {
+                Event::Quit {..} => return false,
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => return true,
+                Event::KeyDown {keycode: Some(Keycode::Return), ..} => play = true,
+                Event::MouseButtonDown {mouse_btn, x, y, ..} => sandbox.start_painting(&layout, mouse_btn, x, y),
+                Event::MouseMotion {x, y, ..} => sandbox.paint(&layout, x, y),
+                Event::MouseButtonUp {..} => sandbox.stop_painting(),
+                Event::KeyDown {keycode: Some(Keycode::Z), keymod, ..} if keymod.intersects(LCTRLMOD | RCTRLMOD) => {
+                    sandbox.undo();
+                },
+                // positions are shared as fumen strings, but both formats can be pasted
+                Event::KeyDown {keycode: Some(Keycode::C), keymod, ..} if keymod.intersects(LCTRLMOD | RCTRLMOD) => {
+                    let result = sandbox.to_position()
+                                        .to_fumen()
+                                        .and_then(|fumen| canvas.window().subsystem().clipboard().set_clipboard_text(&fumen));
+                    match result {
+                        Ok(()) => println!("Position copied to the clipboard"),
+                        Err(e) => println!("Couldn't copy the position: {}", e),
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::V), keymod, ..} if keymod.intersects(LCTRLMOD | RCTRLMOD) => {
+                    let result = canvas.window()
+                                       .subsystem()
+                                       .clipboard()
+                                       .clipboard_text()
+                                       .and_then(|text| Position::import(&text))
+                                       .and_then(|position| sandbox.load_position(&position));
+                    if let Err(e) = result {
+                        println!("Couldn't paste the position: {}", e);
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::Backspace), ..} => {
+                    if !sandbox.pieces.is_empty() {
+                        sandbox.save_state();
+                        sandbox.pieces.pop();
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::G), ..} => sandbox.goal = sandbox.goal.next(),
+                Event::KeyDown {keycode: Some(Keycode::F2), ..} if sandbox.pieces.is_empty() => {
+                    println!("A puzzle needs some pieces");
+                },
+                Event::KeyDown {keycode: Some(Keycode::F2), ..} => {
+                    let mut puzzle = sandbox.to_puzzle();
+                    match puzzle.save_to_sandbox() {
+                        Ok(path) => println!("Puzzle \"{}\" saved to {}", puzzle.name, path.display()),
+                        Err(e) => println!("Couldn't save the puzzle: {}", e),
+                    }
+                },
+                Event::KeyDown {keycode: Some(keycode), ..} => {
+                    let name = keycode.name();
+                    let mut chars = name.chars();
+                    match (chars.next(), chars.next()) {
+                        // number keys select the color
+                        (Some(c), None) if c >= '1' && c <= '7' => sandbox.color = c as u8 - b'0',
+                        (Some(c), None) => {
+                            if let Some(kind) = kind_from_char(c) {
+                                sandbox.save_state();
+                                sandbox.pieces.push(kind);
+                            }
+                        },
+                        _ => {},
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        if play {
+            sandbox.stop_painting();
+            let mut tetris = sandbox.create_game();
+            let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+            let achievements = RefCell::new(AchievementTracker::new(&tetris, &stats, false));
+            let bus = game_bus(&stats, None, Some(&achievements));
+            // the placements can be undone while playing too
+            let mut history = History::new(&tetris);
+            match run_game(&mut tetris, None, &stats, Some(&achievements), &mut Player::Keyboard(&mut *profile),
+                           None, Some(&mut history), None, &bus,
+                           canvas, event_pump, texture_creator, theme) {
+                GameEnd::Closed => return false,
+                _ => println!("Sandbox game: score {}, {} lines", tetris.score, tetris.nb_lines),
+            }
+            layout.update(canvas);
+        }
+
+        draw_sandbox(&sandbox, canvas, texture_creator, theme, &layout);
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
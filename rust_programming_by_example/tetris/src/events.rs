@@ -0,0 +1,105 @@
+use crate::game_board::{GameEvent, Input, Tetris};
+use crate::replay::Replay;
+use crate::stats::Stats;
+
+use std::cell::RefCell;
+
+// Something following a game through the events of the engine (statistics,
+// objectives, sounds...), without the engine knowing about it.
+pub trait GameListener {
+    // `tetris` is the game as it is at the end of the frame the event
+    // happened during
+    fn on_event(&mut self, tetris: &Tetris, event: &GameEvent);
+}
+
+// The listeners of a game, every event going to each of them in the order
+// they subscribed in. The bus is built when the game starts, the listeners
+// being borrowed through a `RefCell` so the game can still look at them
+// between two frames (to show the statistics for example).
+pub struct EventBus<'a> {
+    listeners: Vec<&'a RefCell<dyn GameListener + 'a>>,
+}
+
+impl<'a> EventBus<'a> {
+    pub fn new() -> EventBus<'a> {
+        EventBus {
+            listeners: Vec::new(),
+        }
+    }
+
+    pub fn subscribe(&mut self, listener: &'a RefCell<dyn GameListener + 'a>) {
+        self.listeners.push(listener);
+    }
+
+    // Takes the events of the last simulated frame out of the game and hands
+    // them over to the listeners, none of which may be borrowed meanwhile.
+    pub fn dispatch(&self, tetris: &mut Tetris) {
+        let events = std::mem::take(&mut tetris.events);
+        for event in events.iter() {
+            for listener in self.listeners.iter() {
+                listener.borrow_mut().on_event(tetris, event);
+            }
+        }
+    }
+}
+
+impl<'a> Default for EventBus<'a> {
+    fn default() -> EventBus<'a> {
+        EventBus::new()
+    }
+}
+
+// Simulates one frame of `tetris` with the inputs given during it, then hands
+// the events of the frame over to `bus`. `stats` counts the inputs and the
+// frames and `record` keeps them for a replay. The played games, the replays
+// checked, the ghosts and the simulated games all go through here so they
+// follow the same rules.
+pub fn simulate_frame(tetris: &mut Tetris,
+                      inputs: &[Input],
+                      stats: Option<&RefCell<Stats>>,
+                      record: Option<&mut Replay>,
+                      bus: &EventBus) {
+    if let Some(stats) = stats {
+        let mut stats = stats.borrow_mut();
+        for input in inputs {
+            if tetris.current_piece.is_some() {
+                stats.record_input(*input, tetris);
+            }
+            tetris.apply_input(*input);
+        }
+        tetris.update();
+        stats.tick();
+    } else {
+        for input in inputs {
+            tetris.apply_input(*input);
+        }
+        tetris.update();
+    }
+    if let Some(replay) = record {
+        replay.record_frame(inputs, tetris);
+    }
+    bus.dispatch(tetris);
+}
+
+// Prints the events on stderr as they happen, to follow a game from a
+// terminal without mixing them with the results.
+pub struct EventLog;
+
+impl GameListener for EventLog {
+    fn on_event(&mut self, tetris: &Tetris, event: &GameEvent) {
+        let text = match *event {
+            GameEvent::PieceSpawned { kind } => format!("piece {} spawned", kind),
+            GameEvent::Moved { x } => format!("moved to column {}", x),
+            GameEvent::Rotated { state } => format!("rotated to state {}", state),
+            GameEvent::PieceLocked { kind, x, state } => format!("piece {} locked in column {}, state {}", kind, x, state),
+            GameEvent::LinesCleared { count, kind } => format!("{} lines cleared ({:?})", count, kind),
+            GameEvent::PerfectClear => "perfect clear".to_owned(),
+            GameEvent::LevelUp { level } => format!("level {}", level),
+            GameEvent::GarbageReceived { lines } => format!("{} garbage lines received", lines),
+            GameEvent::GarbageCleared => "garbage cleared".to_owned(),
+            GameEvent::GameOver { reason } => format!("game over: {}", reason.description()),
+            GameEvent::Rewound => "placement taken back".to_owned(),
+        };
+        eprintln!("[score {}, {} lines] {}", tetris.score, tetris.nb_lines, text);
+    }
+}
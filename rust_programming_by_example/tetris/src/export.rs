@@ -0,0 +1,199 @@
+use sdl2::image::{SaveSurface, INIT_PNG};
+use sdl2::pixels::{Color, PixelFormatEnum};
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::surface::{Surface, SurfaceContext};
+use sdl2::ttf::Font;
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::Path;
+
+use crate::game_board::{Tetris, FRAMES_PER_SECOND, GARBAGE, HIDDEN_LINES};
+use crate::gif::GifWriter;
+use crate::layout::Layout;
+use crate::replay::{Playback, Replay};
+use crate::stats::Stats;
+use crate::theme::{DEFAULT_FONT, DEFAULT_PALETTE};
+use crate::tetrimino::create_tetrimino;
+use crate::{game_bus, get_rect_from_text, simulate_frame, NB_PREVIEWS};
+
+// the last frame stays that long before the animation loops
+const END_DELAY_SECS: u32 = 2;
+
+// colors of the classic theme
+const BACKGROUND_COLOR: Color = Color { r: 0, g: 0, b: 255, a: 0xff };
+const GRID_COLOR: Color = Color { r: 0, g: 0, b: 0, a: 0xff };
+const BORDER_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+const TEXT_COLOR: Color = Color { r: 255, g: 255, b: 255, a: 0xff };
+const GRAY_COLOR: Color = Color { r: 150, g: 150, b: 150, a: 0xff };
+
+enum Output {
+    Gif(GifWriter),
+    // directory where the numbered PNG files go
+    Png(String),
+}
+
+fn draw_text(canvas: &mut Canvas<Surface>,
+             texture_creator: &TextureCreator<SurfaceContext>,
+             font: &Font,
+             text: &str,
+             x: i32,
+             y: i32,
+             height: u32) -> Result<(), String> {
+    // not antialiased, to keep the number of colors low in the GIF files
+    let surface = font.render(text).solid(TEXT_COLOR).map_err(|e| e.to_string())?;
+    let texture = texture_creator.create_texture_from_surface(&surface).map_err(|e| e.to_string())?;
+    canvas.copy(&texture, None, get_rect_from_text(font, text, x, y, height))
+}
+
+fn block_color(tetris: &Tetris, case: u8) -> Color {
+    if tetris.modifiers.monochrome || case == GARBAGE {
+        GRAY_COLOR
+    } else {
+        DEFAULT_PALETTE[case as usize - 1]
+    }
+}
+
+// the columns go the other way with the mirror modifier
+fn case_rect(tetris: &Tetris, layout: &Layout, x: i32, y: i32) -> Rect {
+    if tetris.modifiers.mirror {
+        layout.case_rect(layout.nb_columns as i32 - 1 - x, y)
+    } else {
+        layout.case_rect(x, y)
+    }
+}
+
+fn draw_game(canvas: &mut Canvas<Surface>,
+             texture_creator: &TextureCreator<SurfaceContext>,
+             font: &Font,
+             tetris: &Tetris,
+             layout: &Layout) -> Result<(), String> {
+    canvas.set_draw_color(BACKGROUND_COLOR);
+    canvas.clear();
+    canvas.set_draw_color(BORDER_COLOR);
+    canvas.fill_rect(layout.border_rect())?;
+    canvas.set_draw_color(GRID_COLOR);
+    canvas.fill_rect(layout.grid_rect())?;
+
+    for (line_nb, line) in tetris.game_map.iter().skip(HIDDEN_LINES).enumerate() {
+        for (case_nb, case) in line.iter().enumerate() {
+            if *case != 0 {
+                canvas.set_draw_color(block_color(tetris, *case));
+                canvas.fill_rect(case_rect(tetris, layout, case_nb as i32, line_nb as i32))?;
+            }
+        }
+    }
+    if let Some(ref piece) = tetris.current_piece {
+        for (line_nb, line) in piece.states[piece.current_state as usize].iter().enumerate() {
+            for (case_nb, case) in line.iter().enumerate() {
+                if *case != 0 {
+                    canvas.set_draw_color(block_color(tetris, *case));
+                    canvas.fill_rect(case_rect(tetris, layout, (piece.x + case_nb as isize) as i32,
+                                               (piece.y + line_nb) as i32 - HIDDEN_LINES as i32))?;
+                }
+            }
+        }
+    }
+
+    let texts = [format!("Score: {}", tetris.score),
+                 format!("Lines sent: {}", tetris.nb_lines),
+                 format!("Level: {}", tetris.current_level)];
+    for (line_nb, text) in texts.iter().enumerate() {
+        draw_text(canvas, texture_creator, font, text, layout.hud_x, layout.hud_line_y(1 + line_nb as u32),
+                  layout.text_height)?;
+    }
+    if tetris.queue.is_empty() {
+        return Ok(())
+    }
+    draw_text(canvas, texture_creator, font, "Next:", layout.hud_x, layout.hud_line_y(4), layout.text_height)?;
+    let case_size = layout.case_size as i32 / 2;
+    let preview_y = layout.hud_line_y(5);
+    for (piece_nb, kind) in tetris.queue.iter().take(NB_PREVIEWS).enumerate() {
+        let piece = create_tetrimino(*kind);
+        for (line_nb, line) in piece.states[0].iter().enumerate() {
+            for (case_nb, case) in line.iter().enumerate() {
+                if *case != 0 {
+                    canvas.set_draw_color(block_color(tetris, *case));
+                    canvas.fill_rect(Rect::new(layout.hud_x + case_nb as i32 * case_size,
+                                               preview_y + piece_nb as i32 * 3 * case_size + line_nb as i32 * case_size,
+                                               case_size as u32,
+                                               case_size as u32))?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+impl Output {
+    fn add_frame(&mut self, canvas: &Canvas<Surface>, frame_nb: u32, delay: u32) -> Result<(), String> {
+        let (width, height) = canvas.output_size()?;
+        let mut pixels = canvas.read_pixels(None, PixelFormatEnum::RGB24)?;
+        match *self {
+            Output::Gif(ref mut writer) => writer.add_frame(&pixels, delay as u16).map_err(|e| e.to_string()),
+            Output::Png(ref dir) => {
+                let path = Path::new(dir).join(format!("frame_{:05}.png", frame_nb));
+                let surface = Surface::from_data(&mut pixels, width, height, width * 3, PixelFormatEnum::RGB24)?;
+                surface.save(&path).map_err(|e| format!("{}: {}", path.display(), e))
+            },
+        }
+    }
+}
+
+// Plays the replay without a window and writes what would have been seen,
+// `fps` times per second: into an animated GIF if `path` ends with ".gif",
+// as numbered PNG files in the `path` directory otherwise.
+pub fn export_replay(replay: &Replay, path: &str, fps: u32, (width, height): (u32, u32)) -> Result<(), String> {
+    if fps == 0 || fps > FRAMES_PER_SECOND {
+        return Err(format!("the frame rate must be between 1 and {}", FRAMES_PER_SECOND));
+    }
+    let _image_context = sdl2::image::init(INIT_PNG)?;
+    let ttf_context = sdl2::ttf::init().map_err(|e| e.to_string())?;
+    let font = ttf_context.load_font(DEFAULT_FONT, 64)?;
+
+    let mut canvas = Surface::new(width, height, PixelFormatEnum::RGB888)?.into_canvas()?;
+    let texture_creator = canvas.texture_creator();
+    let mut output = if path.ends_with(".gif") {
+        Output::Gif(GifWriter::create(path, width as u16, height as u16).map_err(|e| format!("{}: {}", path, e))?)
+    } else {
+        fs::create_dir_all(path).map_err(|e| format!("{}: {}", path, e))?;
+        Output::Png(path.to_owned())
+    };
+
+    let mut tetris = replay.create_game();
+    let layout = Layout::new(width, height, tetris.game_map.width(), tetris.game_map.len() - HIDDEN_LINES);
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let bus = game_bus(&stats, None, None);
+    let mut playback = Playback::new(replay);
+    let mut frame_nb = 0;
+    let mut simulated_frames = 0;
+    loop {
+        let finished = playback.is_finished() || tetris.is_game_over();
+        // output frames are taken every FRAMES_PER_SECOND / fps simulated
+        // frames, their delays (in hundredths of a second) adding up to the
+        // right duration
+        if finished || simulated_frames * fps >= frame_nb * FRAMES_PER_SECOND {
+            draw_game(&mut canvas, &texture_creator, &font, &tetris, &layout)?;
+            let delay = if finished {
+                END_DELAY_SECS * 100
+            } else {
+                (frame_nb + 1) * 100 / fps - frame_nb * 100 / fps
+            };
+            output.add_frame(&canvas, frame_nb, delay)?;
+            frame_nb += 1;
+        }
+        if finished {
+            break
+        }
+        let inputs = playback.next_frame();
+        simulate_frame(&mut tetris, &inputs, &stats, None, None, &bus);
+        simulated_frames += 1;
+    }
+
+    if let Output::Gif(writer) = output {
+        writer.finish().map_err(|e| format!("{}: {}", path, e))?;
+    }
+    println!("{} frames written to {}", frame_nb, path);
+    Ok(())
+}
@@ -0,0 +1,99 @@
+use crate::board::Board;
+use crate::game_board::Input;
+use crate::tetrimino::{create_tetrimino, spawn_x};
+
+use std::collections::VecDeque;
+
+// Cases covered by the piece once dropped on an empty board: its columns and
+// its lines counted from the bottom. Several states can give the same cases.
+pub fn footprint(kind: u8, x: isize, state: u8) -> Vec<(isize, usize)> {
+    let piece = create_tetrimino(kind);
+    let shape = &piece.states[state as usize];
+    let bottom = shape.iter()
+                      .rposition(|line| line.iter().any(|case| *case != 0))
+                      .unwrap_or(0);
+    let mut cases = Vec::with_capacity(4);
+    for (line_nb, line) in shape.iter().enumerate() {
+        for (case_nb, case) in line.iter().enumerate() {
+            if *case != 0 {
+                cases.push((x + case_nb as isize, bottom - line_nb));
+            }
+        }
+    }
+    cases.sort();
+    cases
+}
+
+// Shortest sequence of inputs (taps, DAS and rotations) bringing a freshly
+// spawned piece to the given column and rotation state on an empty board, or
+// to any other placement covering the same cases.
+pub fn min_input_sequence(kind: u8, target_x: isize, target_state: u8, nb_columns: usize) -> Option<Vec<Input>> {
+    let game_map = Board::new(nb_columns, 4);
+    let target = footprint(kind, target_x, target_state);
+    let mut start = create_tetrimino(kind);
+    start.x = spawn_x(nb_columns);
+    let nb_states = start.states.len();
+    let position_index = |x: isize, state: u8| -> usize {
+        (x + 3) as usize * nb_states + state as usize
+    };
+
+    // x can go a bit negative since the pieces states have empty columns
+    let mut previous: Vec<Option<(isize, u8, Input)>> = vec![None; (nb_columns + 4) * nb_states];
+    let mut visited = vec![false; (nb_columns + 4) * nb_states];
+    let mut to_visit = VecDeque::new();
+    visited[position_index(start.x, start.current_state)] = true;
+    to_visit.push_back((start.x, start.current_state));
+
+    while let Some((x, state)) = to_visit.pop_front() {
+        if footprint(kind, x, state) == target {
+            let mut inputs = Vec::new();
+            let mut position = (x, state);
+            while let Some((x, state, input)) = previous[position_index(position.0, position.1)] {
+                inputs.push(input);
+                position = (x, state);
+            }
+            inputs.reverse();
+            return Some(inputs);
+        }
+
+        let mut neighbours = Vec::with_capacity(5);
+        let mut piece = create_tetrimino(kind);
+        piece.x = x;
+        piece.current_state = state;
+        let fits = |x: isize| piece.test_position(&game_map, state as usize, x, 0);
+        if fits(x - 1) {
+            neighbours.push((x - 1, state, Input::Left));
+            let mut wall_x = x - 1;
+            while fits(wall_x - 1) {
+                wall_x -= 1;
+            }
+            neighbours.push((wall_x, state, Input::DasLeft));
+        }
+        if fits(x + 1) {
+            neighbours.push((x + 1, state, Input::Right));
+            let mut wall_x = x + 1;
+            while fits(wall_x + 1) {
+                wall_x += 1;
+            }
+            neighbours.push((wall_x, state, Input::DasRight));
+        }
+        piece.rotate(&game_map);
+        neighbours.push((piece.x, piece.current_state, Input::Rotate));
+
+        for (next_x, next_state, input) in neighbours {
+            let index = position_index(next_x, next_state);
+            if !visited[index] {
+                visited[index] = true;
+                previous[index] = Some((x, state, input));
+                to_visit.push_back((next_x, next_state));
+            }
+        }
+    }
+    None
+}
+
+// Smallest number of inputs needed to reach the placement, see
+// `min_input_sequence`.
+pub fn min_inputs(kind: u8, target_x: isize, target_state: u8, nb_columns: usize) -> Option<u32> {
+    min_input_sequence(kind, target_x, target_state, nb_columns).map(|inputs| inputs.len() as u32)
+}
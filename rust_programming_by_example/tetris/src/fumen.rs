@@ -0,0 +1,245 @@
+use crate::board::Board;
+use crate::game_board::GARBAGE;
+use crate::position::Position;
+use crate::tetrimino::{kind_from_char, PIECE_NAMES};
+
+const FUMEN_PREFIX: &'static str = "v115@";
+const ENCODE_TABLE: &'static [u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// the fumen field has 23 visible lines and a garbage line below them
+const FIELD_WIDTH: usize = 10;
+const FIELD_HEIGHT: usize = 23;
+const FIELD_BLOCKS: usize = FIELD_WIDTH * (FIELD_HEIGHT + 1);
+
+// fumen block of each piece kind (I L J O S Z T), 8 being the gray blocks
+const FUMEN_BLOCKS: [u8; 7] = [1, 2, 6, 3, 7, 4, 5];
+const GRAY_BLOCK: u8 = 8;
+
+// flags of the page action (rise, mirror, colorize, comment, then one set
+// when the piece doesn't lock)
+const COLORIZE_FLAG: u32 = 4;
+const COMMENT_FLAG: u32 = 8;
+
+// comments are made of the printable ASCII characters, 4 of them packed by 5
+// base 64 digits
+const COMMENT_CHARS: u32 = 96;
+
+// the queue goes in the comment of the page, as "#Q=[hold](current)next"
+const QUEUE_PREFIX: &'static str = "#Q=";
+
+pub fn is_fumen(text: &str) -> bool {
+    text.contains(FUMEN_PREFIX)
+}
+
+fn push_value(data: &mut String, mut value: u32, nb_digits: usize) {
+    for _ in 0..nb_digits {
+        data.push(ENCODE_TABLE[(value % 64) as usize] as char);
+        value /= 64;
+    }
+}
+
+// the opposite of JavaScript's `escape`, for the characters used here
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in text.chars() {
+        if c.is_ascii_alphanumeric() || "@*_+-./".contains(c) {
+            escaped.push(c);
+        } else if (c as u32) < 256 {
+            escaped += &format!("%{:02X}", c as u32);
+        } else {
+            escaped += &format!("%u{:04X}", c as u32);
+        }
+    }
+    escaped
+}
+
+fn unescape(text: &str) -> String {
+    let mut unescaped = String::new();
+    let mut rest = text;
+    while let Some(index) = rest.find('%') {
+        unescaped += &rest[..index];
+        rest = &rest[index..];
+        let (code, length) = if rest.starts_with("%u") {
+            (rest.get(2..6).and_then(|hex| u32::from_str_radix(hex, 16).ok()), 6)
+        } else {
+            (rest.get(1..3).and_then(|hex| u32::from_str_radix(hex, 16).ok()), 3)
+        };
+        match code.and_then(std::char::from_u32) {
+            Some(c) => {
+                unescaped.push(c);
+                rest = &rest[length..];
+            },
+            None => {
+                unescaped.push('%');
+                rest = &rest[1..];
+            },
+        }
+    }
+    unescaped + rest
+}
+
+fn queue_comment(position: &Position) -> String {
+    let name = |kind: Option<u8>| kind.map(|kind| PIECE_NAMES[kind as usize].to_string()).unwrap_or_default();
+    format!("{}[{}]({}){}", QUEUE_PREFIX, name(position.hold), name(position.current),
+            position.queue.iter().map(|kind| PIECE_NAMES[*kind as usize]).collect::<String>())
+}
+
+// Encodes the position as a single page, the pieces going in the comment.
+// The map has to be 10 cases wide and can't be higher than the fumen field.
+pub fn encode(position: &Position) -> Result<String, String> {
+    let game_map = position.board(FIELD_WIDTH, FIELD_HEIGHT)?;
+    let mut field = [0u8; FIELD_BLOCKS];
+    for (line_nb, line) in game_map.iter().enumerate() {
+        for (case_nb, case) in line.iter().enumerate() {
+            field[line_nb * FIELD_WIDTH + case_nb] = match *case {
+                0 => 0,
+                GARBAGE => GRAY_BLOCK,
+                case => FUMEN_BLOCKS[case as usize - 1],
+            };
+        }
+    }
+
+    let mut data = FUMEN_PREFIX.to_owned();
+    // runs of blocks differing the same way from the previous (empty) field
+    let mut start = 0;
+    while start < FIELD_BLOCKS {
+        let mut end = start + 1;
+        while end < FIELD_BLOCKS && field[end] == field[start] {
+            end += 1;
+        }
+        push_value(&mut data, (field[start] as u32 + 8) * FIELD_BLOCKS as u32 + (end - start - 1) as u32, 2);
+        start = end;
+    }
+    // an unchanged field is followed by the number of pages it stays so
+    if field.iter().all(|block| *block == 0) {
+        push_value(&mut data, 0, 1);
+    }
+
+    // no piece on the page, only the comment
+    let comment = escape(&queue_comment(position));
+    push_value(&mut data, (COLORIZE_FLAG | COMMENT_FLAG) * FIELD_BLOCKS as u32 * 4 * 8, 3);
+    let comment = comment.as_bytes();
+    push_value(&mut data, comment.len() as u32, 2);
+    for chunk in comment.chunks(4) {
+        let value = chunk.iter().rev().fold(0, |value, c| value * COMMENT_CHARS + (*c as u32 - 32));
+        push_value(&mut data, value, 5);
+    }
+    Ok(data)
+}
+
+// reads the base 64 digits of a fumen string
+struct Reader<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn read(&mut self, nb_digits: usize) -> Result<u32, String> {
+        let mut value = 0;
+        let mut factor = 1;
+        for _ in 0..nb_digits {
+            let c = *self.data.get(self.index).ok_or("truncated fumen data")?;
+            let digit = ENCODE_TABLE.iter().position(|d| *d == c).ok_or(format!("invalid fumen character '{}'", c as char))?;
+            value += digit as u32 * factor;
+            factor *= 64;
+            self.index += 1;
+        }
+        Ok(value)
+    }
+}
+
+fn parse_queue(comment: &str, position: &mut Position) -> Result<(), String> {
+    let mut rest = match comment.find(QUEUE_PREFIX) {
+        Some(index) => &comment[index + QUEUE_PREFIX.len()..],
+        None => return Ok(()),
+    };
+    let invalid = || format!("invalid queue \"{}\"", comment);
+    // the piece between the bracket starting `rest` and `close`, if any
+    let read_piece = |rest: &mut &str, close: char| -> Result<Option<u8>, String> {
+        let end = rest.find(close).ok_or_else(invalid)?;
+        let piece = match rest[1..end].chars().next() {
+            Some(c) => Some(kind_from_char(c).ok_or_else(invalid)?),
+            None => None,
+        };
+        *rest = &rest[end + 1..];
+        Ok(piece)
+    };
+    if rest.starts_with('[') {
+        position.hold = read_piece(&mut rest, ']')?;
+    }
+    if rest.starts_with('(') {
+        position.current = read_piece(&mut rest, ')')?;
+    }
+    position.queue = rest.chars()
+                         .take_while(|c| !c.is_whitespace() && *c != ';')
+                         .map(|c| kind_from_char(c).ok_or_else(invalid))
+                         .collect::<Result<Vec<_>, String>>()?;
+    Ok(())
+}
+
+// Decodes the first page of a fumen string, the other pages being ignored.
+// The pieces come from the "#Q=" comment if there is one, the piece of the
+// page being the current one otherwise.
+pub fn decode(text: &str) -> Result<Position, String> {
+    let start = text.find(FUMEN_PREFIX).ok_or("not a fumen string")? + FUMEN_PREFIX.len();
+    // URLs can have the data split by question marks
+    let data = text[start..].trim().bytes().filter(|c| *c != b'?').collect::<Vec<_>>();
+    let mut reader = Reader { data: &data, index: 0 };
+
+    let mut field = [0u8; FIELD_BLOCKS];
+    let mut index = 0;
+    while index < FIELD_BLOCKS {
+        let value = reader.read(2)? as usize;
+        let (block, count) = (value / FIELD_BLOCKS, value % FIELD_BLOCKS + 1);
+        if block < 8 || index + count > FIELD_BLOCKS {
+            return Err("invalid fumen field".to_owned());
+        }
+        for field_block in field[index..index + count].iter_mut() {
+            *field_block = (block - 8) as u8;
+        }
+        if index == 0 && count == FIELD_BLOCKS && block == 8 {
+            reader.read(1)?;
+        }
+        index += count;
+    }
+
+    let mut action = reader.read(3)?;
+    let piece_block = action % 8;
+    action /= 8 * 4 * FIELD_BLOCKS as u32;
+    let mut comment = String::new();
+    if action & COMMENT_FLAG != 0 {
+        let length = reader.read(2)? as usize;
+        let mut chars = Vec::with_capacity(length);
+        while chars.len() < length {
+            let mut value = reader.read(5)?;
+            for _ in 0..4 {
+                if chars.len() < length {
+                    chars.push((value % COMMENT_CHARS + 32) as u8 as char);
+                }
+                value /= COMMENT_CHARS;
+            }
+        }
+        comment = unescape(&chars.into_iter().collect::<String>());
+    }
+
+    let mut lines = Vec::with_capacity(FIELD_HEIGHT);
+    for line_nb in 0..FIELD_HEIGHT {
+        let mut line = Vec::with_capacity(FIELD_WIDTH);
+        for block in field[line_nb * FIELD_WIDTH..(line_nb + 1) * FIELD_WIDTH].iter() {
+            line.push(match *block {
+                0 => 0,
+                GRAY_BLOCK => GARBAGE,
+                block => FUMEN_BLOCKS.iter().position(|other| *other == block).ok_or("invalid fumen block")? as u8 + 1,
+            });
+        }
+        lines.push(line);
+    }
+    let mut position = Position {
+        game_map: Board::from_lines(lines)?,
+        current: FUMEN_BLOCKS.iter().position(|block| *block as u32 == piece_block).map(|kind| kind as u8),
+        hold: None,
+        queue: Vec::new(),
+    };
+    parse_queue(&comment, &mut position)?;
+    Ok(position)
+}
@@ -1,90 +1,661 @@
+use crate::board::{Board, MAX_WIDTH};
+use crate::modifiers::Modifiers;
+use crate::tetrimino::{Tetrimino, create_tetrimino, spawn_x};
+use crate::randomizer::Randomizer;
+use crate::trainer::FinesseTrainer;
 
-use crate::tetrimino::Tetrimino;
+use std::collections::VecDeque;
 
-// time intervals between piece movements, changes with level increase
-pub const LEVEL_TIMES: [u32; 10] = [1000, 850, 700, 600, 500, 400, 300, 250, 221, 190];
+// the simulation always runs at this rate, whatever the display does
+pub const FRAMES_PER_SECOND: u32 = 60;
+// number of frames between piece movements, changes with level increase
+pub const LEVEL_FRAMES: [u32; 10] = [60, 51, 42, 36, 30, 24, 18, 15, 13, 11];
+pub const MAX_LEVEL: u32 = 10;
+// limits of the board size, the pieces must be able to spawn
+const MIN_COLUMNS: usize = 6;
+const MIN_LINES: usize = 6;
+const MAX_LINES: usize = 40;
 // number of lines necessary to pass each level
 const LEVEL_LINES: [u32; 10] = [  20,  40,  60,  80, 100, 120, 140, 160, 180, 200];
+// number of upcoming pieces kept in the queue when they are randomly generated
+pub const NB_NEXT_PIECES: usize = 3;
+const MAX_ENTRY_DELAY: u32 = 60;
+// smallest board size in big cases, the pieces needing 4 of them
+const MIN_BIG_CASES: usize = 4;
+// lines above the visible field where the new pieces appear (the vanish zone),
+// not counted in the board size
+pub const HIDDEN_LINES: usize = 2;
+// map value of the garbage cases, the pieces going from 1 to 7
+pub const GARBAGE: u8 = 8;
+// mixed into the game seed for the garbage holes
+const GARBAGE_SEED: u64 = 0x5bd1_e995_9a3f_c2e1;
+// empty lines the starting garbage has to leave
+const MIN_FREE_LINES: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Input {
+    Left,
+    Right,
+    // moves the piece as far as it goes, as a held key does once the delayed
+    // auto shift starts
+    DasLeft,
+    DasRight,
+    // single moves repeated by the auto shift
+    RepeatLeft,
+    RepeatRight,
+    Rotate,
+    SoftDrop,
+    HardDrop,
+    Hold,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameOver {
+    // a new piece overlaps the blocks already there
+    BlockOut,
+    // a piece has been locked entirely above the visible field
+    LockOut,
+    // some blocks are left above the visible field after a piece is locked
+    PartialLockOut,
+    // the queue is empty and the pieces aren't randomly generated
+    NoMorePieces,
+    // rising garbage pushed blocks out of the map
+    TopOut,
+}
+
+impl GameOver {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            GameOver::BlockOut => "block-out",
+            GameOver::LockOut => "lock-out",
+            GameOver::PartialLockOut => "partial-lock-out",
+            GameOver::NoMorePieces => "no-more-pieces",
+            GameOver::TopOut => "top-out",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match *self {
+            GameOver::BlockOut => "Block out",
+            GameOver::LockOut => "Lock out",
+            GameOver::PartialLockOut => "Partial lock out",
+            GameOver::NoMorePieces => "No more pieces",
+            GameOver::TopOut => "Top out",
+        }
+    }
+}
+
+// what a line clear was, in the order of `Stats::clears`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ClearKind {
+    Single,
+    Double,
+    Triple,
+    Tetris,
+    TSpinSingle,
+    TSpinDouble,
+    TSpinTriple,
+}
+
+impl ClearKind {
+    // `count` lines cleared at once, a T can't clear more than three
+    fn new(count: u32, t_spin: bool) -> ClearKind {
+        match (count, t_spin) {
+            (1, false) => ClearKind::Single,
+            (2, false) => ClearKind::Double,
+            (3, false) => ClearKind::Triple,
+            (_, false) => ClearKind::Tetris,
+            (1, true) => ClearKind::TSpinSingle,
+            (2, true) => ClearKind::TSpinDouble,
+            (_, true) => ClearKind::TSpinTriple,
+        }
+    }
+}
+
+// things that happened inside the engine, given to the listeners of the game
+// (see `events::EventBus`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GameEvent {
+    PieceSpawned { kind: u8 },
+    // the current piece went sideways, to the `x` column
+    Moved { x: isize },
+    Rotated { state: u8 },
+    PieceLocked { kind: u8, x: isize, state: u8 },
+    // the level ups these lines gave come after
+    LinesCleared { count: u32, kind: ClearKind },
+    PerfectClear,
+    LevelUp { level: u32 },
+    // rising garbage lines pushed under the stack
+    GarbageReceived { lines: u32 },
+    // the last garbage line has been cleared
+    GarbageCleared,
+    GameOver { reason: GameOver },
+    // the last placement has been taken back (see `History::rewind`)
+    Rewound,
+}
+
+// What a new game depends on, given from the command line or stored in the
+// replays.
+#[derive(Clone)]
+pub struct GameConfig {
+    pub nb_columns: usize,
+    pub nb_lines: usize,
+    pub level: u32,
+    pub seed: u64,
+    // frames between a piece being locked and the next one appearing
+    pub entry_delay: u32,
+    // whether the rotations and holds given while there is no piece apply to
+    // the next one when it appears (IRS and IHS)
+    pub initial_actions: bool,
+    pub modifiers: Modifiers,
+    // garbage lines at the bottom of the map when the game starts
+    pub garbage_lines: usize,
+    // frames between two garbage lines rising from the bottom, 0 for none
+    pub garbage_interval: u32,
+    // finesse training, the board being emptied after every piece
+    pub trainer: bool,
+}
+
+impl GameConfig {
+    pub fn new() -> GameConfig {
+        GameConfig {
+            nb_columns: 10,
+            nb_lines: 16,
+            level: 1,
+            seed: Randomizer::from_entropy().state,
+            entry_delay: 0,
+            initial_actions: true,
+            modifiers: Modifiers::none(),
+            garbage_lines: 0,
+            garbage_interval: 0,
+            trainer: false,
+        }
+    }
+
+    // size of the game map without the vanish zone, big mode halving the
+    // board given in cases
+    pub fn board_size(&self) -> (usize, usize) {
+        if self.modifiers.big {
+            (self.nb_columns / 2, self.nb_lines / 2)
+        } else {
+            (self.nb_columns, self.nb_lines)
+        }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.nb_columns < MIN_COLUMNS || self.nb_columns > MAX_WIDTH {
+            return Err(format!("the board must be {} to {} cases wide", MIN_COLUMNS, MAX_WIDTH));
+        }
+        if self.nb_lines < MIN_LINES || self.nb_lines > MAX_LINES {
+            return Err(format!("the board must be {} to {} lines high", MIN_LINES, MAX_LINES));
+        }
+        if self.level < 1 || self.level > MAX_LEVEL {
+            return Err(format!("the level must be between 1 and {}", MAX_LEVEL));
+        }
+        let (nb_columns, nb_lines) = self.board_size();
+        if self.modifiers.big && (nb_columns < MIN_BIG_CASES || nb_lines < MIN_BIG_CASES) {
+            return Err(format!("big mode needs a board of at least {0}x{0} cases", MIN_BIG_CASES * 2));
+        }
+        if self.garbage_lines + MIN_FREE_LINES > nb_lines {
+            return Err(format!("the garbage must leave {} empty lines", MIN_FREE_LINES));
+        }
+        if self.entry_delay > MAX_ENTRY_DELAY {
+            return Err(format!("the entry delay can't be more than {} frames", MAX_ENTRY_DELAY));
+        }
+        Ok(())
+    }
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        GameConfig::new()
+    }
+}
 
 pub struct Tetris {
-    pub game_map: Vec<Vec<u8>>,
+    pub game_map: Board,
     pub current_level: u32,
     pub score: u32,
     pub nb_lines: u32,
     pub current_piece: Option<Tetrimino>,
+    // pieces having appeared so far, the held ones included
+    pub spawned_pieces: u32,
+    pub queue: VecDeque<u8>,
+    pub hold: Option<u8>,
+    // only one hold is allowed per piece
+    pub can_hold: bool,
+    // when false, the game ends once the queue is empty
+    pub random_pieces: bool,
+    pub randomizer: Randomizer,
+    // frames spent since the current piece last fell
+    pub drop_frames: u32,
+    pub entry_delay: u32,
+    // frames spent waiting for the next piece
+    pub entry_frames: u32,
+    pub initial_actions: bool,
+    // rotation and hold asked while waiting for the next piece
+    pub initial_rotation: bool,
+    pub initial_hold: bool,
+    pub modifiers: Modifiers,
+    pub garbage_interval: u32,
+    // frames spent since the last garbage line rose
+    pub garbage_frames: u32,
+    // where the holes of the garbage lines are, kept apart from the pieces
+    // so the piece sequence doesn't depend on the garbage
+    pub garbage_randomizer: Randomizer,
+    pub trainer: Option<FinesseTrainer>,
+    pub game_over: Option<GameOver>,
+    pub events: Vec<GameEvent>,
 }
 
 impl Tetris {
     pub fn new() -> Tetris {
-        let mut game_map = Vec::new();
-        for _ in 0..16 {
-            game_map.push(vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
-        }
+        Tetris::from_config(&GameConfig::new())
+    }
 
-        Tetris {
-            game_map: game_map,
-            current_level: 1,
+    pub fn from_config(config: &GameConfig) -> Tetris {
+        let mut randomizer = Randomizer::new(config.seed);
+        let (nb_columns, nb_lines) = config.board_size();
+        let mut tetris = Tetris {
+            game_map: Board::new(nb_columns, nb_lines + HIDDEN_LINES),
+            current_level: config.level,
             score: 0,
             nb_lines: 0,
             current_piece: None,
+            spawned_pieces: 0,
+            queue: (0..NB_NEXT_PIECES).map(|_| randomizer.next_kind()).collect(),
+            hold: None,
+            can_hold: true,
+            random_pieces: true,
+            randomizer: randomizer,
+            drop_frames: 0,
+            entry_delay: config.entry_delay,
+            entry_frames: 0,
+            initial_actions: config.initial_actions,
+            initial_rotation: false,
+            initial_hold: false,
+            modifiers: config.modifiers,
+            garbage_interval: config.garbage_interval,
+            garbage_frames: 0,
+            garbage_randomizer: Randomizer::new(config.seed ^ GARBAGE_SEED),
+            trainer: if config.trainer { Some(FinesseTrainer::new(config.seed, nb_columns)) } else { None },
+            game_over: None,
+            events: Vec::new(),
+        };
+        for _ in 0..config.garbage_lines {
+            tetris.add_garbage_line();
+        }
+        tetris
+    }
+
+    pub fn is_game_over(&self) -> bool {
+        self.game_over.is_some()
+    }
+
+    // Checks a game restored from outside (a save) the way `GameConfig::check`
+    // checks a new one, along with the pieces and the map.
+    pub fn check(&self) -> Result<(), String> {
+        let (min_width, min_lines) = if self.modifiers.big {
+            (MIN_BIG_CASES, MIN_BIG_CASES)
+        } else {
+            (MIN_COLUMNS, MIN_LINES)
+        };
+        if self.game_map.width() < min_width || self.game_map.width() > MAX_WIDTH {
+            return Err(format!("the map must be {} to {} cases wide", min_width, MAX_WIDTH));
+        }
+        if self.game_map.len() < min_lines + HIDDEN_LINES || self.game_map.len() > MAX_LINES + HIDDEN_LINES {
+            return Err(format!("the map must be {} to {} lines high", min_lines + HIDDEN_LINES,
+                               MAX_LINES + HIDDEN_LINES));
+        }
+        if let Some(case) = self.game_map.iter().flat_map(|line| line.iter()).find(|case| **case > GARBAGE) {
+            return Err(format!("invalid case {}", case));
+        }
+        // passing the last LEVEL_LINES goes one level past MAX_LEVEL
+        if self.current_level < 1 || self.current_level > MAX_LEVEL + 1 {
+            return Err(format!("the level must be between 1 and {}", MAX_LEVEL + 1));
+        }
+        if self.entry_delay > MAX_ENTRY_DELAY {
+            return Err(format!("the entry delay can't be more than {} frames", MAX_ENTRY_DELAY));
+        }
+        if let Some(kind) = self.queue.iter().chain(self.hold.iter()).find(|kind| **kind >= 7) {
+            return Err(format!("invalid piece kind {}", kind));
+        }
+        // 7 before the first piece
+        if self.randomizer.previous > 7 {
+            return Err(format!("invalid previous piece {}", self.randomizer.previous));
+        }
+        if let Some(ref piece) = self.current_piece {
+            if piece.kind() >= 7 || piece.current_state as usize >= piece.states.len() {
+                return Err("invalid current piece".to_owned());
+            }
+            if !piece.test_current_position(&self.game_map) {
+                return Err("the current piece overlaps the map".to_owned());
+            }
+        }
+        Ok(())
+    }
+
+    // the map without the vanish zone
+    pub fn visible_map(&self) -> Board {
+        self.game_map.without_lines_above(HIDDEN_LINES)
+    }
+
+    pub fn set_visible_map(&mut self, game_map: &Board) {
+        self.game_map = game_map.with_lines_above(HIDDEN_LINES);
+    }
+
+    fn end_game(&mut self, reason: GameOver) {
+        self.game_over = Some(reason);
+        self.current_piece = None;
+        self.events.push(GameEvent::GameOver { reason: reason });
+    }
+
+    pub fn has_garbage(&self) -> bool {
+        self.game_map.iter().any(|line| line.contains(&GARBAGE))
+    }
+
+    // Pushes a garbage line with a random hole from the bottom, the current
+    // piece going up with the rest if it doesn't fit anymore.
+    fn add_garbage_line(&mut self) {
+        let nb_columns = self.game_map.width();
+        let mut line = vec![GARBAGE; nb_columns];
+        line[self.garbage_randomizer.next_below(nb_columns)] = 0;
+        if !self.game_map.push_line(&line) {
+            self.end_game(GameOver::TopOut);
+            return
+        }
+        let mut pushed_out = false;
+        if let Some(ref mut piece) = self.current_piece {
+            if !piece.test_current_position(&self.game_map) {
+                let (x, y) = (piece.x, piece.y);
+                pushed_out = y == 0 || !piece.change_position(&self.game_map, x, y - 1);
+            }
+        }
+        if pushed_out {
+            self.end_game(GameOver::TopOut);
         }
     }
 
-    fn check_lines(&mut self) {
-        let mut y = 0;
-        let mut score_add = 0;
+    fn create_piece(&self, kind: u8) -> Tetrimino {
+        let mut piece = create_tetrimino(kind);
+        piece.x = spawn_x(self.game_map.width());
+        piece
+    }
+
+    pub fn next_piece(&mut self) -> Option<Tetrimino> {
+        let piece = self.queue.pop_front().map(|kind| self.create_piece(kind));
+        if self.random_pieces {
+            while self.queue.len() < NB_NEXT_PIECES {
+                self.queue.push_back(self.randomizer.next_kind());
+            }
+        }
+        piece
+    }
+
+    fn gravity_frames(&self) -> u32 {
+        LEVEL_FRAMES[(self.current_level as usize).min(LEVEL_FRAMES.len() - 1)]
+    }
 
-        while y < self.game_map.len() {
-            let mut complete = true;
-            for x in &self.game_map[y]{
-                if *x == 0 {
-                    complete = false;
-                    break
+    // advances the game by one frame
+    pub fn update(&mut self) {
+        if self.is_game_over() {
+            return
+        }
+        if self.garbage_interval > 0 {
+            self.garbage_frames += 1;
+            if self.garbage_frames >= self.garbage_interval {
+                self.garbage_frames = 0;
+                self.events.push(GameEvent::GarbageReceived { lines: 1 });
+                self.add_garbage_line();
+                if self.is_game_over() {
+                    return
                 }
             }
-            if complete {
-                score_add += self.current_level;
-                self.game_map.remove(y);
-                y -= 1;
+        }
+        if self.current_piece.is_none() {
+            if self.entry_frames < self.entry_delay {
+                self.entry_frames += 1;
+                return
+            }
+            self.entry_frames = 0;
+            match self.next_piece() {
+                Some(piece) => {
+                    let mut piece = Some(piece);
+                    if self.initial_hold {
+                        piece = self.swap_hold(piece);
+                    }
+                    if self.initial_rotation {
+                        if let Some(ref mut piece) = piece {
+                            piece.rotate(&self.game_map);
+                        }
+                    }
+                    let rotated = self.initial_rotation;
+                    self.initial_hold = false;
+                    self.initial_rotation = false;
+                    self.spawn(piece);
+                    if rotated {
+                        if let Some(ref mut trainer) = self.trainer {
+                            trainer.record_input(Input::Rotate);
+                        }
+                    }
+                },
+                None => self.end_game(GameOver::NoMorePieces),
+            }
+            return
+        }
+
+        self.drop_frames += 1;
+        if self.drop_frames >= self.gravity_frames() {
+            self.drop_frames = 0;
+            let mut make_permanent = false;
+            if let Some(ref mut piece) = self.current_piece {
+                let x = piece.x;
+                let y = piece.y + 1;
+                make_permanent = !piece.change_position(&self.game_map, x, y);
+            }
+            if make_permanent {
+                self.make_permanent();
+            }
+        }
+    }
+
+    // The piece appears in the vanish zone and goes down right away if it can.
+    // Having no piece here means there are no more of them.
+    fn spawn(&mut self, piece: Option<Tetrimino>) {
+        match piece {
+            Some(mut piece) => {
+                if let Some(ref mut trainer) = self.trainer {
+                    trainer.start_piece(piece.kind());
+                }
+                if piece.test_current_position(&self.game_map) {
+                    let (x, y) = (piece.x, piece.y);
+                    piece.change_position(&self.game_map, x, y + 1);
+                    self.events.push(GameEvent::PieceSpawned { kind: piece.kind() });
+                    self.current_piece = Some(piece);
+                    self.spawned_pieces += 1;
+                } else {
+                    self.end_game(GameOver::BlockOut);
+                }
+            },
+            None => self.end_game(GameOver::NoMorePieces),
+        }
+    }
+
+    // Puts `piece` aside, giving back the one held before or the next one.
+    fn swap_hold(&mut self, piece: Option<Tetrimino>) -> Option<Tetrimino> {
+        let piece = match piece {
+            Some(piece) if self.can_hold => piece,
+            piece => return piece,
+        };
+        self.can_hold = false;
+        match self.hold.replace(piece.kind()) {
+            Some(kind) => Some(self.create_piece(kind)),
+            None => self.next_piece(),
+        }
+    }
+
+    pub fn apply_input(&mut self, input: Input) {
+        if input == Input::Rotate && self.modifiers.no_rotation {
+            return
+        }
+        if self.current_piece.is_none() {
+            // kept for the next piece, giving them several times doesn't add up
+            if self.initial_actions && !self.is_game_over() {
+                match input {
+                    Input::Rotate => self.initial_rotation = true,
+                    Input::Hold => self.initial_hold = true,
+                    _ => {},
+                }
+            }
+            return
+        }
+        if input == Input::Hold {
+            if self.can_hold {
+                let piece = self.current_piece.take();
+                let piece = self.swap_hold(piece);
+                self.drop_frames = 0;
+                self.spawn(piece);
             }
-            y += 1;
+            return
         }
-        if self.game_map.len() == 0 {
+
+        if let Some(ref mut trainer) = self.trainer {
+            trainer.record_input(input);
+        }
+
+        let mut make_permanent = false;
+        if let Some(ref mut piece) = self.current_piece {
+            let (x, y, state) = (piece.x, piece.y, piece.current_state);
+            match input {
+                Input::Left | Input::RepeatLeft => {
+                    piece.change_position(&self.game_map, x - 1, y);
+                },
+                Input::Right | Input::RepeatRight => {
+                    piece.change_position(&self.game_map, x + 1, y);
+                },
+                Input::DasLeft => {
+                    let mut x = x;
+                    while piece.change_position(&self.game_map, x - 1, y) {
+                        x -= 1;
+                    }
+                },
+                Input::DasRight => {
+                    let mut x = x;
+                    while piece.change_position(&self.game_map, x + 1, y) {
+                        x += 1;
+                    }
+                },
+                Input::Rotate => piece.rotate(&self.game_map),
+                Input::SoftDrop => {
+                    self.drop_frames = 0;
+                    make_permanent = !piece.change_position(&self.game_map, x, y + 1);
+                },
+                Input::HardDrop => {
+                    let distance = piece.drop_distance(&self.game_map);
+                    piece.change_position(&self.game_map, x, y + distance);
+                    make_permanent = true;
+                },
+                Input::Hold => {},
+            }
+            // a rotation can move the piece away from a wall too
+            if piece.current_state != state {
+                self.events.push(GameEvent::Rotated { state: piece.current_state });
+            } else if piece.x != x {
+                self.events.push(GameEvent::Moved { x: piece.x });
+            }
+        }
+        if make_permanent {
+            self.make_permanent();
+            self.drop_frames = 0;
+        }
+    }
+
+    // how far the current piece went towards the next line, `frame_fraction`
+    // being the part of the current frame already elapsed (used to draw the
+    // falling piece smoothly)
+    pub fn fall_progress(&self, frame_fraction: f32) -> f32 {
+        match self.current_piece {
+            Some(ref piece) if piece.test_position(&self.game_map, piece.current_state as usize, piece.x, piece.y + 1) => {
+                ((self.drop_frames as f32 + frame_fraction) / self.gravity_frames() as f32).min(1.)
+            },
+            _ => 0.,
+        }
+    }
+
+    fn check_lines(&mut self) -> u32 {
+        let nb_cleared = self.game_map.clear_lines();
+        let mut score_add = nb_cleared * self.current_level;
+        if nb_cleared as usize == self.game_map.len() - HIDDEN_LINES {
             score_add += 1000;
         }
         self.update_score(score_add);
-        while self.game_map.len() < 16 {
+        for _ in 0..nb_cleared {
             self.increase_line();
-            self.game_map.insert(0, vec![0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
         }
+        nb_cleared
     }
 
     pub fn make_permanent(&mut self) {
         let mut to_add = 0;
+        let mut t_spin = false;
+        let mut lock_out = false;
+        // the piece to give again when the trainer target hasn't been done
+        let mut retry = None;
         if let Some(ref mut piece) = self.current_piece {
-            let mut shift_y = 0;
-
-            while shift_y < piece.states[piece.current_state as usize].len()
-                && piece.y + shift_y < self.game_map.len() {
-
-                let mut shift_x = 0;
-
-                while shift_x < piece.states[piece.current_state as usize][shift_y].len()
-                    && (piece.x + shift_x as isize) < self.game_map[piece.y + shift_y].len() as isize {
-
-                    if piece.states[piece.current_state as usize][shift_y][shift_x] != 0 {
+            if let Some(ref mut trainer) = self.trainer {
+                if !trainer.evaluate(piece.kind(), piece.x, piece.current_state) {
+                    retry = Some(piece.kind());
+                }
+            }
+            t_spin = piece.is_t_spin(&self.game_map);
+            self.events.push(GameEvent::PieceLocked {
+                kind: piece.kind(),
+                x: piece.x,
+                state: piece.current_state,
+            });
+            lock_out = true;
+            for (shift_y, line) in piece.states[piece.current_state as usize].iter().enumerate() {
+                for (shift_x, case) in line.iter().enumerate() {
+                    if *case != 0 {
                         let x = piece.x + shift_x as isize;
-                        self.game_map[piece.y + shift_y][x as usize] = piece.states[piece.current_state as usize][shift_y][shift_x];
+                        self.game_map.set(x as usize, piece.y + shift_y, *case);
+                        lock_out &= piece.y + shift_y < HIDDEN_LINES;
                     }
-                    shift_x += 1;
                 }
-                shift_y += 1;
             }
             to_add += self.current_level;
         }
         self.update_score(to_add);
-        self.check_lines();
+        let had_garbage = self.has_garbage();
+        let level = self.current_level;
+        let count = self.check_lines();
+        if count > 0 {
+            self.events.push(GameEvent::LinesCleared { count, kind: ClearKind::new(count, t_spin) });
+            for level in level + 1..=self.current_level {
+                self.events.push(GameEvent::LevelUp { level });
+            }
+            if self.game_map.is_empty() {
+                self.events.push(GameEvent::PerfectClear);
+            }
+            if had_garbage && !self.has_garbage() {
+                self.events.push(GameEvent::GarbageCleared);
+            }
+        }
         self.current_piece = None;
+        self.can_hold = true;
+        if self.trainer.is_some() {
+            // every target is given on an empty board
+            self.game_map = Board::new(self.game_map.width(), self.game_map.len());
+            if let Some(kind) = retry {
+                self.queue.push_front(kind);
+            }
+        }
+
+        if lock_out {
+            self.end_game(GameOver::LockOut);
+        } else if (0..HIDDEN_LINES).any(|y| self.game_map[y].iter().any(|case| *case != 0)) {
+            self.end_game(GameOver::PartialLockOut);
+        }
     }
 
     fn update_score(&mut self, to_add: u32) {
@@ -93,8 +664,16 @@ impl Tetris {
 
     fn increase_line(&mut self) {
         self.nb_lines += 1;
-        if self.nb_lines > LEVEL_LINES[self.current_level as usize - 1] {
-            self.current_level += 1;
+        if let Some(level_lines) = LEVEL_LINES.get(self.current_level as usize - 1) {
+            if self.nb_lines > *level_lines {
+                self.current_level += 1;
+            }
         }
     }
-}
\ No newline at end of file
+}
+
+impl Default for Tetris {
+    fn default() -> Tetris {
+        Tetris::new()
+    }
+}
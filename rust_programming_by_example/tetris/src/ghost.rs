@@ -0,0 +1,83 @@
+use crate::events::{simulate_frame, EventBus};
+use crate::game_board::{Tetris, FRAMES_PER_SECOND};
+use crate::replay::{Playback, Replay, Verification};
+
+// the times are compared every this many lines
+pub const SPLIT_LINES: u32 = 10;
+
+// adds the splits reached by a game having `nb_lines` lines after `frame`
+// frames
+fn add_splits(splits: &mut Vec<u32>, nb_lines: u32, frame: u32) {
+    while (splits.len() as u32 + 1) * SPLIT_LINES <= nb_lines {
+        splits.push(frame);
+    }
+}
+
+// A recorded best game played back next to the live one, the times at which
+// both reach every SPLIT_LINES lines being compared.
+pub struct Ghost<'a> {
+    pub tetris: Tetris,
+    playback: Playback<'a>,
+    // frames at which the best game and the live one reached each split
+    best_splits: Vec<u32>,
+    splits: Vec<u32>,
+    // how the best game ended
+    pub best_score: u32,
+    pub best_lines: u32,
+}
+
+impl<'a> Ghost<'a> {
+    // `verification` is what `replay.verify()` gave: the whole best game is
+    // needed first, the live one can get ahead
+    pub fn new(replay: &'a Replay, verification: &Verification) -> Ghost<'a> {
+        let mut best_splits = Vec::new();
+        for (line_nb, frame) in verification.line_frames.iter().enumerate() {
+            add_splits(&mut best_splits, line_nb as u32 + 1, *frame);
+        }
+        Ghost {
+            tetris: replay.create_game(),
+            playback: Playback::new(replay),
+            best_splits: best_splits,
+            splits: Vec::new(),
+            best_score: verification.score,
+            best_lines: verification.nb_lines,
+        }
+    }
+
+    // Advances the best game by one frame, along with the live one. Returns
+    // whether the live game has just reached a split.
+    pub fn update(&mut self, live: &Tetris, frame: u32) -> bool {
+        if !self.playback.is_finished() && !self.tetris.is_game_over() {
+            simulate_frame(&mut self.tetris, &self.playback.next_frame(), None, None, &EventBus::new());
+        }
+        let nb_splits = self.splits.len();
+        add_splits(&mut self.splits, live.nb_lines, frame);
+        self.splits.len() > nb_splits
+    }
+
+    fn split_text(&self, split: usize) -> String {
+        let lines = (split as u32 + 1) * SPLIT_LINES;
+        match self.best_splits.get(split) {
+            Some(best) => {
+                let difference = self.splits[split] as f64 - *best as f64;
+                format!("{} lines: {:+.2} s", lines, difference / FRAMES_PER_SECOND as f64)
+            },
+            None => format!("{} lines: further than the best", lines),
+        }
+    }
+
+    // difference with the best game at the last split reached, negative
+    // when ahead
+    pub fn last_split_text(&self) -> Option<String> {
+        if self.splits.is_empty() {
+            None
+        } else {
+            Some(self.split_text(self.splits.len() - 1))
+        }
+    }
+
+    // every split reached, for the results screen
+    pub fn summary_lines(&self) -> Vec<String> {
+        (0..self.splits.len()).map(|split| self.split_text(split)).collect()
+    }
+}
@@ -0,0 +1,179 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+// codes of the LZW compression are at most 12 bits long
+const MAX_CODES: u16 = 4096;
+
+// Writes an animated GIF looping forever, frame after frame. Each frame has
+// its own color table: the exact colors when there are 256 of them at most,
+// a 6x7x6 color cube otherwise.
+pub struct GifWriter {
+    output: BufWriter<File>,
+    width: u16,
+    height: u16,
+}
+
+impl GifWriter {
+    pub fn create(path: &str, width: u16, height: u16) -> io::Result<GifWriter> {
+        let mut output = BufWriter::new(File::create(path)?);
+        output.write_all(b"GIF89a")?;
+        output.write_all(&width.to_le_bytes())?;
+        output.write_all(&height.to_le_bytes())?;
+        // no global color table, background color 0, no aspect ratio
+        output.write_all(&[0, 0, 0])?;
+        // loop forever
+        output.write_all(&[0x21, 0xff, 11])?;
+        output.write_all(b"NETSCAPE2.0")?;
+        output.write_all(&[3, 1, 0, 0, 0])?;
+        Ok(GifWriter {
+            output: output,
+            width: width,
+            height: height,
+        })
+    }
+
+    // `pixels` are RGB bytes, `delay` is in hundredths of a second
+    pub fn add_frame(&mut self, pixels: &[u8], delay: u16) -> io::Result<()> {
+        let (colors, indices) = index_colors(pixels);
+
+        // graphic control extension, for the delay
+        self.output.write_all(&[0x21, 0xf9, 4, 0])?;
+        self.output.write_all(&delay.to_le_bytes())?;
+        self.output.write_all(&[0, 0])?;
+
+        // image descriptor with a local color table of 256 entries
+        self.output.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.output.write_all(&self.width.to_le_bytes())?;
+        self.output.write_all(&self.height.to_le_bytes())?;
+        self.output.write_all(&[0x87])?;
+        let mut table = vec![0; 256 * 3];
+        for (index, color) in colors.iter().enumerate() {
+            table[index * 3..index * 3 + 3].copy_from_slice(color);
+        }
+        self.output.write_all(&table)?;
+
+        self.output.write_all(&[8])?;
+        for block in compress(&indices).chunks(255) {
+            self.output.write_all(&[block.len() as u8])?;
+            self.output.write_all(block)?;
+        }
+        self.output.write_all(&[0])
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.output.write_all(&[0x3b])?;
+        self.output.flush()
+    }
+}
+
+fn index_colors(pixels: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut colors = Vec::new();
+    let mut color_indices = HashMap::new();
+    let mut indices = Vec::with_capacity(pixels.len() / 3);
+    for pixel in pixels.chunks(3) {
+        let color = [pixel[0], pixel[1], pixel[2]];
+        let index = match color_indices.get(&color) {
+            Some(index) => *index,
+            None => {
+                if colors.len() == 256 {
+                    return color_cube(pixels);
+                }
+                let index = colors.len() as u8;
+                color_indices.insert(color, index);
+                colors.push(color);
+                index
+            },
+        };
+        indices.push(index);
+    }
+    (colors, indices)
+}
+
+fn color_cube(pixels: &[u8]) -> (Vec<[u8; 3]>, Vec<u8>) {
+    let mut colors = Vec::with_capacity(252);
+    for r in 0..6 {
+        for g in 0..7 {
+            for b in 0..6 {
+                colors.push([(r * 255 / 5) as u8, (g * 255 / 6) as u8, (b * 255 / 5) as u8]);
+            }
+        }
+    }
+    let indices = pixels.chunks(3)
+                        .map(|pixel| {
+                            let r = (pixel[0] as u32 * 5 + 127) / 255;
+                            let g = (pixel[1] as u32 * 6 + 127) / 255;
+                            let b = (pixel[2] as u32 * 5 + 127) / 255;
+                            (r * 42 + g * 6 + b) as u8
+                        })
+                        .collect();
+    (colors, indices)
+}
+
+// packs the codes, least significant bits first
+struct BitWriter {
+    bytes: Vec<u8>,
+    current: u32,
+    nb_bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, size: u32) {
+        self.current |= (code as u32) << self.nb_bits;
+        self.nb_bits += size;
+        while self.nb_bits >= 8 {
+            self.bytes.push(self.current as u8);
+            self.current >>= 8;
+            self.nb_bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.nb_bits > 0 {
+            self.bytes.push(self.current as u8);
+        }
+        self.bytes
+    }
+}
+
+// LZW compression of 8 bits color indices
+fn compress(indices: &[u8]) -> Vec<u8> {
+    let clear_code: u16 = 256;
+    let end_code: u16 = 257;
+    let mut writer = BitWriter { bytes: Vec::new(), current: 0, nb_bits: 0 };
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = 9;
+
+    writer.write(clear_code, code_size);
+    let mut prefix = match indices.first() {
+        Some(index) => *index as u16,
+        None => {
+            writer.write(end_code, code_size);
+            return writer.finish();
+        },
+    };
+    for index in indices[1..].iter() {
+        if let Some(code) = table.get(&(prefix, *index)) {
+            prefix = *code;
+            continue
+        }
+        writer.write(prefix, code_size);
+        if next_code == MAX_CODES {
+            writer.write(clear_code, code_size);
+            table.clear();
+            next_code = end_code + 1;
+            code_size = 9;
+        } else {
+            if next_code >= 1 << code_size {
+                code_size += 1;
+            }
+            table.insert((prefix, *index), next_code);
+            next_code += 1;
+        }
+        prefix = *index as u16;
+    }
+    writer.write(prefix, code_size);
+    writer.write(end_code, code_size);
+    writer.finish()
+}
@@ -0,0 +1,152 @@
+use crate::events::GameListener;
+use crate::game_board::{GameEvent, Tetris};
+use crate::modifiers::Modifiers;
+
+use std::fs;
+
+const NB_HIGHSCORES: usize = 5;
+
+// the best results of the games played with the same modifiers
+struct Table {
+    modifiers: Modifiers,
+    scores: Vec<u32>,
+    lines: Vec<u32>,
+}
+
+// The highscores are kept apart for every set of modifiers so only the scores
+// of comparable games get compared.
+pub struct Highscores {
+    tables: Vec<Table>,
+}
+
+fn slice_to_string(slice: &[u32]) -> String {
+    slice
+        .iter()
+        .map(|highscore| highscore.to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn line_to_slice(line: &str) -> Vec<u32> {
+    line.split(" ")
+        .filter_map(|nb| nb.parse::<u32>().ok())
+        .collect()
+}
+
+fn update_vec(v: &mut Vec<u32>, value: u32) -> bool {
+    if v.len() < NB_HIGHSCORES {
+        v.push(value);
+        v.sort();
+        true
+    } else {
+        for entry in v.iter_mut() {
+            if value > *entry {
+                *entry = value;
+                return true;
+            }
+        }
+        false
+    }
+
+}
+
+impl Highscores {
+    // The file has a line of scores followed by a line of numbers of lines for
+    // the games without modifiers, then the same after a "modifiers" line for
+    // every other set of modifiers played:
+    //
+    // 120 300 450
+    // 2 5 8
+    // modifiers big,mirror
+    // 80
+    // 1
+    pub fn parse(content: &str) -> Highscores {
+        let mut tables = Vec::new();
+        // None when the modifiers of the table are unknown
+        let mut modifiers = Some(Modifiers::none());
+        let mut values = Vec::new();
+        for line in content.lines() {
+            if line.starts_with("modifiers ") {
+                modifiers = Modifiers::parse(&line["modifiers ".len()..]).ok();
+                values.clear();
+                continue
+            }
+            values.push(line_to_slice(line));
+            if values.len() == 2 {
+                let lines = values.pop().unwrap();
+                let scores = values.pop().unwrap();
+                if let Some(modifiers) = modifiers {
+                    tables.push(Table { modifiers: modifiers, scores: scores, lines: lines });
+                }
+            }
+        }
+        Highscores { tables: tables }
+    }
+
+    // an unreadable file gives no highscores
+    pub fn load(file_name: &str) -> Highscores {
+        Highscores::parse(&fs::read_to_string(file_name).unwrap_or_default())
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut content = String::new();
+        for table in self.tables.iter() {
+            if !table.modifiers.is_none() {
+                content += &format!("modifiers {}\n", table.modifiers.to_text());
+            }
+            content += &format!("{}\n{}\n", slice_to_string(&table.scores), slice_to_string(&table.lines));
+        }
+        content
+    }
+
+    pub fn save(&self, file_name: &str) -> bool {
+        fs::write(file_name, self.to_text()).is_ok()
+    }
+
+    // Adds the result of a game. Returns whether the score and the number of
+    // lines are new highscores.
+    pub fn add(&mut self, modifiers: Modifiers, score: u32, nb_lines: u32) -> (bool, bool) {
+        match self.tables.iter_mut().find(|table| table.modifiers == modifiers) {
+            Some(table) => (update_vec(&mut table.scores, score), update_vec(&mut table.lines, nb_lines)),
+            None => {
+                self.tables.push(Table { modifiers: modifiers, scores: vec![score], lines: vec![nb_lines] });
+                (true, true)
+            },
+        }
+    }
+}
+
+// Submits the result of a game to the highscores once it's over, unless
+// placements have been taken back.
+pub struct HighscoreSubmission {
+    file_name: String,
+    rewound: bool,
+    // whether the score and the number of lines are new highscores
+    pub new_highscores: (bool, bool),
+}
+
+impl HighscoreSubmission {
+    pub fn new(file_name: &str) -> HighscoreSubmission {
+        HighscoreSubmission {
+            file_name: file_name.to_owned(),
+            rewound: false,
+            new_highscores: (false, false),
+        }
+    }
+}
+
+impl GameListener for HighscoreSubmission {
+    fn on_event(&mut self, tetris: &Tetris, event: &GameEvent) {
+        match *event {
+            GameEvent::Rewound => self.rewound = true,
+            GameEvent::GameOver { .. } if !self.rewound => {
+                let mut highscores = Highscores::load(&self.file_name);
+                self.new_highscores = highscores.add(tetris.modifiers, tetris.score, tetris.nb_lines);
+                if (self.new_highscores.0 || self.new_highscores.1) && !highscores.save(&self.file_name) {
+                    println!("Couldn't save the highscores");
+                }
+            },
+            _ => {},
+        }
+    }
+}
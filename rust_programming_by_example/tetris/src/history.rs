@@ -0,0 +1,103 @@
+use crate::board::Board;
+use crate::game_board::{GameEvent, Tetris};
+use crate::randomizer::Randomizer;
+
+use std::collections::VecDeque;
+
+// placements that can be taken back, the oldest ones being forgotten
+const MAX_SNAPSHOTS: usize = 200;
+
+// The engine state right after a piece has been locked, the next piece being
+// drawn from the restored queue and randomizer as it was the first time.
+#[derive(Clone)]
+pub struct Snapshot {
+    game_map: Board,
+    queue: VecDeque<u8>,
+    hold: Option<u8>,
+    // the piece spawned in the frame it was taken, already out of the queue
+    current_kind: Option<u8>,
+    score: u32,
+    nb_lines: u32,
+    current_level: u32,
+    randomizer: Randomizer,
+    garbage_frames: u32,
+    garbage_randomizer: Randomizer,
+}
+
+impl Snapshot {
+    pub fn take(tetris: &Tetris) -> Snapshot {
+        Snapshot {
+            game_map: tetris.game_map.clone(),
+            queue: tetris.queue.clone(),
+            hold: tetris.hold,
+            current_kind: tetris.current_piece.as_ref().map(|piece| piece.kind()),
+            score: tetris.score,
+            nb_lines: tetris.nb_lines,
+            current_level: tetris.current_level,
+            randomizer: tetris.randomizer.clone(),
+            garbage_frames: tetris.garbage_frames,
+            garbage_randomizer: tetris.garbage_randomizer.clone(),
+        }
+    }
+
+    // the piece being played, if any, is dropped
+    pub fn restore(&self, tetris: &mut Tetris) {
+        tetris.game_map = self.game_map.clone();
+        tetris.queue = self.queue.clone();
+        if let Some(kind) = self.current_kind {
+            tetris.queue.push_front(kind);
+        }
+        tetris.hold = self.hold;
+        tetris.can_hold = true;
+        tetris.score = self.score;
+        tetris.nb_lines = self.nb_lines;
+        tetris.current_level = self.current_level;
+        tetris.randomizer = self.randomizer.clone();
+        tetris.garbage_frames = self.garbage_frames;
+        tetris.garbage_randomizer = self.garbage_randomizer.clone();
+        tetris.current_piece = None;
+        tetris.drop_frames = 0;
+        tetris.entry_frames = 0;
+        tetris.initial_rotation = false;
+        tetris.initial_hold = false;
+        tetris.events.clear();
+    }
+}
+
+// Snapshots of a practice game, one per locked piece, so the placements can
+// be taken back.
+pub struct History {
+    snapshots: VecDeque<Snapshot>,
+    // number of placements taken back since the game started
+    pub nb_rewinds: u32,
+}
+
+impl History {
+    // the game as it starts, before any piece has been locked
+    pub fn new(tetris: &Tetris) -> History {
+        History {
+            snapshots: VecDeque::from(vec![Snapshot::take(tetris)]),
+            nb_rewinds: 0,
+        }
+    }
+
+    // to be called once a piece has been locked
+    pub fn record(&mut self, tetris: &Tetris) {
+        if self.snapshots.len() >= MAX_SNAPSHOTS {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(Snapshot::take(tetris));
+    }
+
+    // Takes the last placement back. Returns false if there is none left.
+    pub fn rewind(&mut self, tetris: &mut Tetris) -> bool {
+        if self.snapshots.len() < 2 {
+            return false
+        }
+        self.snapshots.pop_back();
+        self.snapshots[self.snapshots.len() - 1].restore(tetris);
+        tetris.events.push(GameEvent::Rewound);
+        self.nb_rewinds += 1;
+        true
+    }
+}
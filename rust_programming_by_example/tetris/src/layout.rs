@@ -0,0 +1,122 @@
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::Keycode;
+use sdl2::rect::Rect;
+use sdl2::render::Canvas;
+use sdl2::video::{FullscreenType, Window};
+
+use std::cmp::{max, min};
+
+// width of the information panel, in number of cases
+const HUD_CASES: u32 = 8;
+
+// Positions and sizes of everything drawn on screen, computed from the
+// current window size so the game scales with it.
+pub struct Layout {
+    pub case_size: u32,
+    pub border: u32,
+    pub grid_x: i32,
+    pub grid_y: i32,
+    pub nb_columns: u32,
+    pub nb_lines: u32,
+    pub hud_x: i32,
+    pub text_height: u32,
+}
+
+impl Layout {
+    pub fn new(width: u32, height: u32, nb_columns: usize, nb_lines: usize) -> Layout {
+        let nb_columns = nb_columns as u32;
+        let nb_lines = nb_lines as u32;
+        let margin = max(height / 40, 4);
+
+        // one extra case is kept for the border and the margins
+        let case_size = max(min((height.saturating_sub(2 * margin)) / (nb_lines + 1),
+                                (width.saturating_sub(3 * margin)) / (nb_columns + 1 + HUD_CASES)),
+                            4);
+        let border = max(case_size / 4, 1);
+        let total_width = nb_columns * case_size + 2 * border + margin + HUD_CASES * case_size;
+        let grid_x = (width.saturating_sub(total_width) / 2 + border) as i32;
+        let grid_y = (height.saturating_sub(nb_lines * case_size) / 2) as i32;
+
+        Layout {
+            case_size: case_size,
+            border: border,
+            grid_x: grid_x,
+            grid_y: grid_y,
+            nb_columns: nb_columns,
+            nb_lines: nb_lines,
+            hud_x: grid_x + (nb_columns * case_size + border + margin) as i32,
+            text_height: max(case_size * 3 / 4, 8),
+        }
+    }
+
+    pub fn from_canvas(canvas: &Canvas<Window>, nb_columns: usize, nb_lines: usize) -> Layout {
+        let (width, height) = canvas.output_size().expect("Couldn't get the window size");
+        Layout::new(width, height, nb_columns, nb_lines)
+    }
+
+    pub fn update(&mut self, canvas: &Canvas<Window>) {
+        *self = Layout::from_canvas(canvas, self.nb_columns as usize, self.nb_lines as usize);
+    }
+
+    pub fn grid_rect(&self) -> Rect {
+        Rect::new(self.grid_x, self.grid_y, self.nb_columns * self.case_size, self.nb_lines * self.case_size)
+    }
+
+    pub fn border_rect(&self) -> Rect {
+        Rect::new(self.grid_x - self.border as i32,
+                  self.grid_y - self.border as i32,
+                  self.nb_columns * self.case_size + 2 * self.border,
+                  self.nb_lines * self.case_size + 2 * self.border)
+    }
+
+    pub fn case_rect(&self, x: i32, y: i32) -> Rect {
+        Rect::new(self.grid_x + x * self.case_size as i32,
+                  self.grid_y + y * self.case_size as i32,
+                  self.case_size,
+                  self.case_size)
+    }
+
+    // case under the given point of the window, if any
+    pub fn case_at(&self, x: i32, y: i32) -> Option<(usize, usize)> {
+        if x < self.grid_x || y < self.grid_y {
+            return None
+        }
+        let case_x = ((x - self.grid_x) / self.case_size as i32) as u32;
+        let case_y = ((y - self.grid_y) / self.case_size as i32) as u32;
+        if case_x < self.nb_columns && case_y < self.nb_lines {
+            Some((case_x as usize, case_y as usize))
+        } else {
+            None
+        }
+    }
+
+    // vertical position of the given line of text in the information panel
+    pub fn hud_line_y(&self, line: u32) -> i32 {
+        self.grid_y + (line * (self.text_height + self.text_height / 6)) as i32
+    }
+}
+
+pub fn toggle_fullscreen(canvas: &mut Canvas<Window>) {
+    let window = canvas.window_mut();
+    let new_state = match window.fullscreen_state() {
+        FullscreenType::Off => FullscreenType::Desktop,
+        _ => FullscreenType::Off,
+    };
+    if let Err(e) = window.set_fullscreen(new_state) {
+        println!("Couldn't change the fullscreen mode: {}", e);
+    }
+}
+
+// Handles the events common to every screen (resizing and the F11 fullscreen
+// toggle). Returns true if the window size may have changed.
+pub fn handle_window_event(event: &Event, canvas: &mut Canvas<Window>) -> bool {
+    match *event {
+        Event::Window {win_event: WindowEvent::Resized(..), ..} |
+        Event::Window {win_event: WindowEvent::SizeChanged(..), ..} => true,
+        Event::KeyDown {keycode: Some(Keycode::F11), repeat: false, ..} => {
+            toggle_fullscreen(canvas);
+            true
+        },
+        _ => false,
+    }
+}
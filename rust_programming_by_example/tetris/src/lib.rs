@@ -0,0 +1,19 @@
+// The game engine, without anything related to the display, so it can be
+// shared by the game and the simulator.
+
+pub mod board;
+pub mod tetrimino;
+pub mod game_board;
+pub mod events;
+pub mod randomizer;
+pub mod finesse;
+pub mod stats;
+pub mod replay;
+pub mod ai;
+pub mod position;
+pub mod fumen;
+pub mod modifiers;
+pub mod trainer;
+pub mod history;
+pub mod achievements;
+pub mod ghost;
@@ -1,144 +1,234 @@
 extern crate sdl2;
 
-mod tetrimino;
-mod game_board;
+use tetris::{achievements, ai, board, events, finesse, game_board, ghost, history, modifiers, position, randomizer, replay, stats, tetrimino, trainer};
+
+mod puzzle;
+mod menu;
+mod layout;
+mod theme;
+mod settings;
+mod save;
+mod cli;
+mod gif;
+mod export;
+mod editor;
+mod highscores;
+mod profiles;
+mod daily;
 
 use sdl2::event::Event;
-use sdl2::image::{LoadTexture, INIT_JPG, INIT_PNG};
-use sdl2::keyboard::Keycode;
+use sdl2::image::{INIT_JPG, INIT_PNG};
+use sdl2::keyboard::{Keycode, LCTRLMOD, RCTRLMOD};
 use sdl2::pixels::Color;
 use sdl2::render::{TextureCreator, Canvas, Texture};
+use sdl2::ttf::Sdl2TtfContext;
 use sdl2::video::{Window, WindowContext};
 use sdl2::rect::Rect;
+use sdl2::EventPump;
 
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant};
 
-use std::fs::File;
-use std::io::{self, Read, Write};
+use std::io::{self, Write};
 
-use tetrimino::create_new_tetrimino;
-use game_board::Tetris;
-use game_board::LEVEL_TIMES;
+use board::Board;
+use tetrimino::create_tetrimino;
+use game_board::{GameConfig, Tetris};
+use game_board::{Input, FRAMES_PER_SECOND, GARBAGE, HIDDEN_LINES};
+use events::{EventBus, EventLog};
+use menu::{read_key, read_text, select_from_menu, show_results, Answer, MenuAction};
+use puzzle::{Goal, Objective, PuzzlePack};
+use layout::{handle_window_event, Layout};
+use theme::{load_theme, list_themes, DisplayOptions, Palette, Theme, THEMES_DIR};
+use settings::{on_off, Settings};
+use stats::Stats;
+use history::History;
+use ghost::Ghost;
+use finesse::footprint;
+use trainer::Target;
+use highscores::HighscoreSubmission;
+use daily::{Challenge, DailyScores};
+use achievements::{AchievementTracker, ACHIEVEMENTS};
+use profiles::{format_date, Action, Controls, Profile, Profiles, ACTION_NAMES, MAX_ARR_FRAMES, MAX_DAS_FRAMES, MIN_DAS_FRAMES};
+use modifiers::{Modifiers, MODIFIER_NAMES};
+use position::Position;
+use replay::{Playback, Replay};
+use ai::Bot;
+use cli::{Mode, Options};
 
-const NB_HIGHSCORES: usize = 5;
-const TETRIS_HEIGHT: usize = 40;
-const HIGHSCORE_FILE: &'static str = "scores.txt";
+use std::cell::RefCell;
+use std::path::Path;
 
-fn write_to_file(contents: String, file_name: &str) -> io::Result<()> {
-    let mut f = File::create(file_name)?;
-    f.write_all(contents.as_bytes())
-}
+// number of upcoming pieces shown next to the grid
+const NB_PREVIEWS: usize = 3;
+// size of the block textures, they get scaled to the layout case size
+const BLOCK_TEXTURE_SIZE: u32 = 40;
+// frames during which the invisible stack shows after a line clear
+const FLASH_FRAMES: u32 = 30;
+// longest profile name
+const MAX_NAME_LEN: usize = 16;
+// frames during which an unlocked achievement is shown
+const TOAST_FRAMES: u32 = 180;
+const SPRINT_LINES: u32 = 40;
+const ULTRA_SECS: u32 = 180;
+const HIGHSCORE_FILE: &'static str = "scores.txt";
+const DAILY_SCORE_FILE: &'static str = "daily_scores.txt";
+// statistics of every recorded game, one line each
+const STATS_FILE: &'static str = "scores_stats.txt";
 
-fn read_from_file(file_name: &str) -> io::Result<String> {
-    let mut f = File::open(file_name)?;
-    let mut content = String::new();
-    f.read_to_string(&mut content)?;
-    Ok(content)
-}
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / FRAMES_PER_SECOND as u64);
+// drawing more often than this is useless (used when vsync is not available)
+const MIN_FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 240);
+// the simulation doesn't try to catch up with more than this (after the
+// window has been moved for example)
+const MAX_LAG: Duration = Duration::from_millis(250);
 
-fn slice_to_string(slice: &[u32]) -> String {
-    slice
-        .iter()
-        .map(|highscore| highscore.to_string())
-        .collect::<Vec<String>>()
-        .join(" ")
+enum GameEnd {
+    ToppedOut,
+    // the player pressed escape
+    Quit,
+    // the window was closed
+    Closed,
+    // the puzzle objective was reached
+    Completed,
+    // all the inputs of the replay have been played
+    ReplayEnded,
 }
 
-fn line_to_slice(line: &str) -> Vec<u32> {
-    line.split(" ")
-        .filter_map(|nb| nb.parse::<u32>().ok())
-        .collect()
+// where the inputs of a game come from
+enum Player<'a> {
+    // the achievements get unlocked in the profile
+    Keyboard(&'a mut Profile),
+    Bot(Bot),
+    Replay(Playback<'a>),
 }
 
-fn save_highscores_and_lines(highscores: &[u32], number_of_lines: &[u32]) -> bool {
-    let s_highscores = slice_to_string(highscores);
-    let s_number_of_lines = slice_to_string(number_of_lines);
-
-    write_to_file(format!("{}\n{}\n", s_highscores, s_number_of_lines), HIGHSCORE_FILE).is_ok()
+#[derive(Clone, Copy, PartialEq)]
+enum MainEntry {
+    Continue,
+    Profile,
+    Marathon,
+    Puzzles,
+    Practice,
+    Sprint,
+    Ultra,
+    Dig,
+    Trainer,
+    Daily,
+    Sandbox,
+    Modifiers,
+    Options,
+    Quit,
 }
 
-fn load_highscores_and_lines() -> Option<(Vec<u32>, Vec<u32>)> {
-    if let Ok(content) = read_from_file(HIGHSCORE_FILE) {
-        let mut lines = content.splitn(2, "\n")
-                               .map(|line| line_to_slice(line))
-                               .collect::<Vec<_>>();
-        if lines.len() == 2 {
-            let (number_lines, highscores) = (lines.pop().unwrap(), lines.pop().unwrap());
-            Some((highscores, number_lines))
-        } else {
-            None
+// turns the pressed keys into game inputs and the number of placements to
+// take back, returns how the game should end if the player quit
+fn handle_events(inputs: &mut Vec<Input>, rewinds: &mut u32, controls: &Controls, event_pump: &mut sdl2::EventPump,
+                 canvas: &mut Canvas<Window>, layout: &mut Layout) -> Option<GameEnd> {
+    for event in event_pump.poll_iter() {
+        if handle_window_event(&event, canvas) {
+            layout.update(canvas);
+            continue
+        }
+        match event {
+            Event::Quit {..} => return Some(GameEnd::Closed),
+            Event::KeyDown {keycode: Some(Keycode::Escape), ..} => return Some(GameEnd::Quit),
+            // undoes the last placement, as in the sandbox editor
+            Event::KeyDown {keycode: Some(Keycode::Z), keymod, ..} if keymod.intersects(LCTRLMOD | RCTRLMOD) => {
+                *rewinds += 1;
+            },
+            Event::KeyDown {keycode: Some(keycode), repeat, ..} => {
+                match controls.action(keycode) {
+                    // holding them is handled by the auto shift
+                    Some(Action::Left) if !repeat => inputs.push(Input::Left),
+                    Some(Action::Right) if !repeat => inputs.push(Input::Right),
+                    Some(Action::Rotate) => inputs.push(Input::Rotate),
+                    Some(Action::SoftDrop) => inputs.push(Input::SoftDrop),
+                    Some(Action::HardDrop) => inputs.push(Input::HardDrop),
+                    Some(Action::Hold) => inputs.push(Input::Hold),
+                    Some(Action::Rewind) => *rewinds += 1,
+                    _ => {}
+                }
+            },
+            _ => {}
         }
-    } else {
-        None
     }
+    None
 }
 
-fn update_vec(v: &mut Vec<u32>, value: u32) -> bool {
-    if v.len() < NB_HIGHSCORES {
-        v.push(value);
-        v.sort();
-        true
-    } else {
-        for entry in v.iter_mut() {
-            if value > *entry {
-                *entry = value;
-                return true;
-            }
-        }
-        false
+// The rotation and hold keys still held while waiting for a piece apply to it
+// when it appears, as pressing them would.
+fn add_held_inputs(event_pump: &EventPump, controls: &Controls, inputs: &mut Vec<Input>) {
+    let keyboard = event_pump.keyboard_state();
+    if controls.is_held(&keyboard, Action::Rotate) {
+        inputs.push(Input::Rotate);
     }
+    if controls.is_held(&keyboard, Action::Hold) {
+        inputs.push(Input::Hold);
+    }
+}
 
+// Delayed auto shift: a side key held long enough moves the piece on its own,
+// straight against the wall or a case every few frames depending on the
+// profile.
+struct AutoShift {
+    direction: Option<Action>,
+    // frames the side key has been held
+    frames: u32,
 }
 
-fn handle_events(tetris: &mut Tetris, quit: &mut bool, timer: &mut SystemTime, event_pump: &mut sdl2::EventPump) -> bool {
-    let mut make_permanent = false;
-    if let Some(ref mut piece) = tetris.current_piece {
-        let mut tmp_x = piece.x;
-        let mut tmp_y = piece.y;
+impl AutoShift {
+    fn new() -> AutoShift {
+        AutoShift {
+            direction: None,
+            frames: 0,
+        }
+    }
 
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit {..} |
-                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => {
-                    *quit = true;
-                    break
-                },
-                Event::KeyDown {keycode: Some(Keycode::Down), ..} => {
-                    *timer = SystemTime::now();
-                    tmp_y += 1;
-                },
-                Event::KeyDown {keycode: Some(Keycode::Right), ..} => {
-                     tmp_x += 1;
-                },
-                Event::KeyDown {keycode: Some(Keycode::Left), ..} => {
-                    tmp_x -= 1;
-                },
-                Event::KeyDown {keycode: Some(Keycode::Up), ..} => {
-                    piece.rotate(&tetris.game_map);
-                },
-                Event::KeyDown {keycode: Some(Keycode::Space), ..} => {
-                    let x = piece.x;
-                    let mut y = piece.y;
-                    while piece.change_position(&tetris.game_map, x, y + 1) {
-                        y += 1;
-                    }
-                    make_permanent = true;
-                },
-                _ => {}
-            }
+    // to be called once per simulated frame
+    fn update(&mut self, event_pump: &EventPump, controls: &Controls, inputs: &mut Vec<Input>) {
+        let keyboard = event_pump.keyboard_state();
+        let direction = if controls.is_held(&keyboard, Action::Left) {
+            Some(Action::Left)
+        } else if controls.is_held(&keyboard, Action::Right) {
+            Some(Action::Right)
+        } else {
+            None
+        };
+        if direction != self.direction {
+            self.direction = direction;
+            self.frames = 0;
+            return
         }
-        if !make_permanent {
-            if !piece.change_position(&tetris.game_map, tmp_x, tmp_y)
-                && tmp_y != piece.y {
-                make_permanent = true;
+        let left = match direction {
+            Some(direction) => direction == Action::Left,
+            None => return,
+        };
+        self.frames += 1;
+        if self.frames < controls.das_frames {
+            return
+        }
+        let repeat_frames = self.frames - controls.das_frames;
+        if controls.arr_frames == 0 {
+            if repeat_frames == 0 {
+                inputs.push(if left { Input::DasLeft } else { Input::DasRight });
             }
+        } else if repeat_frames % controls.arr_frames == 0 {
+            inputs.push(if left { Input::RepeatLeft } else { Input::RepeatRight });
         }
     }
-    if make_permanent {
-        tetris.make_permanent();
-        *timer = SystemTime::now();
+}
+
+// with the mirror modifier, left and right follow the mirrored board
+fn mirror_input(input: Input) -> Input {
+    match input {
+        Input::Left => Input::Right,
+        Input::Right => Input::Left,
+        Input::DasLeft => Input::DasRight,
+        Input::DasRight => Input::DasLeft,
+        Input::RepeatLeft => Input::RepeatRight,
+        Input::RepeatRight => Input::RepeatLeft,
+        input => input,
     }
-    make_permanent
 }
 
 fn create_texture_rect<'a>(canvas: &mut Canvas<Window>,
@@ -168,87 +258,1205 @@ fn create_texture_from_text<'a>(texture_creator: &'a TextureCreator<WindowContex
     }
 }
 
-fn get_rect_from_text(text: &str, x: i32, y: i32) -> Option<Rect> {
-    Some(Rect::new(x, y, text.len() as u32 * 20, 30))
+fn get_rect_from_text(font: &sdl2::ttf::Font, text: &str, x: i32, y: i32, height: u32) -> Option<Rect> {
+    // the text is rendered at the font size, scale it to the wanted height
+    let (text_width, text_height) = font.size_of(text).ok()?;
+    if text_height == 0 {
+        return None
+    }
+    Some(Rect::new(x, y, text_width * height / text_height, height))
 }
 
-fn print_information(tetris: &Tetris) {
-    let mut new_highest_highscore = true;
-    let mut new_highest_lines_sent = true;
-
-    if let Some((mut highscores, mut lines_sent)) = load_highscores_and_lines() {
-        new_highest_highscore = update_vec(&mut highscores, tetris.score);
-        new_highest_lines_sent = update_vec(&mut lines_sent, tetris.nb_lines);
+fn save_stats(tetris: &Tetris, stats: &Stats) -> io::Result<()> {
+    let mut f = std::fs::OpenOptions::new().create(true).append(true).open(STATS_FILE)?;
+    writeln!(f, "score={} lines={} level={} modifiers={} {}", tetris.score, tetris.nb_lines, tetris.current_level,
+             tetris.modifiers.to_text(), stats.to_record())
+}
 
-        if new_highest_lines_sent || new_highest_highscore {
-            save_highscores_and_lines(&highscores, &lines_sent);
-        }
-    } else {
-        save_highscores_and_lines(&[tetris.score], &[tetris.nb_lines]);
-    }
+// `highscores` is given when the game results went to the highscores.
+fn print_information(tetris: &Tetris, stats: &Stats, highscores: Option<&HighscoreSubmission>) {
+    let (new_highest_highscore, new_highest_lines_sent) = highscores.map_or((false, false),
+                                                                            |highscores| highscores.new_highscores);
     println!("Game over!");
     println!("Score:           {}{}", tetris.score
                                     , if new_highest_highscore { " [NEW HIGHSCORE]" } else { "" });
     println!("Number of lines: {}{}", tetris.nb_lines
                                     , if new_highest_lines_sent { " [NEW HIGHSCORE]" } else { "" });
     println!("Current level:   {}", tetris.current_level);
+    if !tetris.modifiers.is_none() {
+        println!("Modifiers:       {}", tetris.modifiers.names().join(", "));
+    }
+    for line in stats.summary_lines() {
+        println!("{}", line);
+    }
+    if save_stats(tetris, stats).is_err() {
+        println!("Couldn't save the game statistics");
+    }
 }
 
-fn display_game_information<'a>(tetris: &Tetris, canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, font: &sdl2::ttf::Font, start_x_point: i32) {
+fn display_game_information<'a>(tetris: &Tetris, objective: Option<&Objective>, stats: &Stats, canvas: &mut Canvas<Window>, texture_creator: &'a TextureCreator<WindowContext>, theme: &Theme, layout: &Layout) {
+    let start_x_point = layout.hud_x;
+    let font = &theme.font;
+    if let Some(objective) = objective {
+        let goal_text = objective.description();
+        let goal = create_texture_from_text(&texture_creator, &font, &goal_text, theme.highlight_color)
+            .expect("Couldn't render the goal text");
+        canvas.copy(&goal, None, get_rect_from_text(&font, &goal_text, start_x_point, layout.hud_line_y(0), layout.text_height))
+            .expect("Couldn't copy the goal text");
+    }
+
     let score_text = format!("Score: {}", tetris.score);
     let lines_sent_text = format!("Lines sent: {}", tetris.nb_lines);
     let level_text = format!("Level: {}", tetris.current_level);
 
-    let score = create_texture_from_text(&texture_creator, &font, &score_text, Color::RGB(255, 255, 255))
+    let score = create_texture_from_text(&texture_creator, &font, &score_text, theme.text_color)
         .expect("Couldn't render the score text");
-    let lines_sent = create_texture_from_text(&texture_creator, &font, &lines_sent_text, Color::RGB(255, 255, 255))
+    let lines_sent = create_texture_from_text(&texture_creator, &font, &lines_sent_text, theme.text_color)
         .expect("Couldn't render the lines sent text");
-    let level = create_texture_from_text(&texture_creator, &font, &level_text, Color::RGB(255, 255, 255))
+    let level = create_texture_from_text(&texture_creator, &font, &level_text, theme.text_color)
         .expect("Couldn't render the level text");
 
-    canvas.copy(&score, None, get_rect_from_text(&score_text, start_x_point, 90))
+    canvas.copy(&score, None, get_rect_from_text(&font, &score_text, start_x_point, layout.hud_line_y(1), layout.text_height))
         .expect("Couldn't copy the score text");
-    canvas.copy(&lines_sent, None, get_rect_from_text(&lines_sent_text, start_x_point, 125))
+    canvas.copy(&lines_sent, None, get_rect_from_text(&font, &lines_sent_text, start_x_point, layout.hud_line_y(2), layout.text_height))
         .expect("Couldn't copy the lines sent text");
-    canvas.copy(&level, None, get_rect_from_text(&level_text, start_x_point, 160))
+    canvas.copy(&level, None, get_rect_from_text(&font, &level_text, start_x_point, layout.hud_line_y(3), layout.text_height))
         .expect("Couldn't copy the level text");
+
+    // the trainer tells how the current piece is going instead
+    let live_lines = match tetris.trainer {
+        Some(ref trainer) => trainer.live_lines(),
+        None => stats.live_lines(),
+    };
+    for (line_nb, stats_text) in live_lines.iter().enumerate() {
+        let stats_line = create_texture_from_text(&texture_creator, &font, stats_text, theme.text_color)
+            .expect("Couldn't render the stats text");
+        canvas.copy(&stats_line, None, get_rect_from_text(&font, stats_text, start_x_point,
+                                                          layout.hud_line_y(11 + line_nb as u32), layout.text_height))
+            .expect("Couldn't copy the stats text");
+    }
+
+    let case_size = layout.case_size as i32 / 2;
+    let preview_y = layout.hud_line_y(5);
+    if let Some(kind) = tetris.hold {
+        // next to the queue, the pieces being at most 4 cases wide
+        let hold_x = start_x_point + 5 * case_size;
+        let hold_text = "Hold:";
+        let hold = create_texture_from_text(&texture_creator, &font, hold_text, theme.text_color)
+            .expect("Couldn't render the hold text");
+        canvas.copy(&hold, None, get_rect_from_text(&font, hold_text, hold_x, layout.hud_line_y(4), layout.text_height))
+            .expect("Couldn't copy the hold text");
+        draw_preview(canvas, theme, &tetris.modifiers, kind, hold_x, preview_y, case_size);
+    }
+
+    if tetris.queue.is_empty() {
+        return
+    }
+    let next_text = "Next:";
+    let next = create_texture_from_text(&texture_creator, &font, next_text, theme.text_color)
+        .expect("Couldn't render the next text");
+    canvas.copy(&next, None, get_rect_from_text(&font, next_text, start_x_point, layout.hud_line_y(4), layout.text_height))
+        .expect("Couldn't copy the next text");
+
+    for (piece_nb, kind) in tetris.queue.iter().take(NB_PREVIEWS).enumerate() {
+        draw_preview(canvas, theme, &tetris.modifiers, *kind, start_x_point,
+                     preview_y + piece_nb as i32 * 3 * case_size, case_size);
+    }
+}
+
+// Draws a piece of the given kind in its spawn state, its top left corner at
+// (x, y), mirrored and colored as the board is.
+fn draw_preview(canvas: &mut Canvas<Window>, theme: &Theme, modifiers: &Modifiers, kind: u8, x: i32, y: i32, case_size: i32) {
+    let piece = create_tetrimino(kind);
+    for (line_nb, line) in piece.states[0].iter().enumerate() {
+        for (case_nb, case) in line.iter().enumerate() {
+            if *case == 0 {
+                continue
+            }
+            let case_nb = if modifiers.mirror { line.len() - 1 - case_nb } else { case_nb };
+            canvas.copy(block_texture(theme, modifiers, *case), None,
+                        Rect::new(x + case_nb as i32 * case_size,
+                                  y + line_nb as i32 * case_size,
+                                  case_size as u32,
+                                  case_size as u32))
+                .expect("Couldn't copy the preview piece texture into canvas");
+        }
+    }
+}
+
+fn block_texture<'t>(theme: &'t Theme, modifiers: &Modifiers, case: u8) -> &'t Texture<'t> {
+    if modifiers.monochrome || case == GARBAGE {
+        &theme.gray_block
+    } else {
+        &theme.blocks[case as usize - 1]
+    }
+}
+
+// Draws a block at the (x, y) case of the grid, moved down by `offset_y`
+// pixels. In big mode, a case looks like 2x2 blocks.
+fn draw_case(canvas: &mut Canvas<Window>, theme: &Theme, layout: &Layout, modifiers: &Modifiers,
+             case: u8, x: i32, y: i32, offset_y: i32) {
+    let texture = block_texture(theme, modifiers, case);
+    let x = if modifiers.mirror { layout.nb_columns as i32 - 1 - x } else { x };
+    let mut rect = layout.case_rect(x, y);
+    rect.offset(0, offset_y);
+    if modifiers.big {
+        let half = rect.width() / 2;
+        for &(decal_x, decal_y) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            canvas.copy(texture, None, Rect::new(rect.x() + decal_x * half as i32, rect.y() + decal_y * half as i32,
+                                                 half, half))
+                .expect("Couldn't copy the block texture into canvas");
+        }
+    } else {
+        canvas.copy(texture, None, rect)
+            .expect("Couldn't copy the block texture into canvas");
+    }
+}
+
+// outlines where the trainer wants the current piece to go
+fn draw_target(canvas: &mut Canvas<Window>, theme: &Theme, layout: &Layout, modifiers: &Modifiers, target: &Target) {
+    canvas.set_draw_color(theme.highlight_color);
+    for (x, line) in footprint(target.kind, target.x, target.state) {
+        let x = if modifiers.mirror { layout.nb_columns as i32 - 1 - x as i32 } else { x as i32 };
+        canvas.draw_rect(layout.case_rect(x, layout.nb_lines as i32 - 1 - line as i32))
+            .expect("Couldn't draw the target");
+    }
+}
+
+// draws the background, the grid and the placed pieces, only the bottom lines
+// of the map fitting in the layout being visible
+fn draw_board(canvas: &mut Canvas<Window>, theme: &Theme, layout: &Layout, game_map: &Board, modifiers: &Modifiers) {
+    theme.draw_background(canvas);
+    canvas.copy(&theme.border, None, layout.border_rect())
+        .expect("Couldn't copy border texture into window");
+
+    canvas.copy(&theme.grid, None, layout.grid_rect())
+        .expect("Couldn't copy grid texture into window");
+
+    if modifiers.invisible {
+        return
+    }
+    let hidden_lines = game_map.len().saturating_sub(layout.nb_lines as usize);
+    for (line_nb, line) in game_map.iter().skip(hidden_lines).enumerate() {
+        for (case_nb, case) in line.iter().enumerate() {
+            if *case == 0 {
+                continue
+            }
+            draw_case(canvas, theme, layout, modifiers, *case, case_nb as i32, line_nb as i32, 0);
+        }
+    }
+}
+
+// How far the live game and the ghost are towards the goal, from 0 to 1: the
+// lines to clear, or the best score when racing against the clock.
+fn race_progress(goal: Goal, tetris: &Tetris, ghost: &Ghost) -> (f64, f64) {
+    let (live, best, target) = match goal {
+        Goal::ClearLines(nb) => (tetris.nb_lines, ghost.tetris.nb_lines, nb),
+        _ => (tetris.score, ghost.tetris.score, ghost.best_score.max(tetris.score)),
+    };
+    let target = target.max(1) as f64;
+    ((live as f64 / target).min(1.), (best as f64 / target).min(1.))
+}
+
+// Draws the progress of the live game over the ghost's one, with the last
+// split, under the statistics.
+fn draw_race(canvas: &mut Canvas<Window>,
+             texture_creator: &TextureCreator<WindowContext>,
+             theme: &Theme,
+             layout: &Layout,
+             tetris: &Tetris,
+             goal: Goal,
+             ghost: &Ghost) {
+    let font = &theme.font;
+    let (live, best) = race_progress(goal, tetris, ghost);
+    let width = layout.case_size * 6;
+    let height = (layout.text_height / 3).max(2);
+    let y = layout.hud_line_y(15);
+    // the ghost dimmed, under the live game
+    let text = theme.text_color;
+    let bars = [(live, theme.highlight_color, y),
+                (best, Color::RGB(text.r / 2, text.g / 2, text.b / 2), y + height as i32 + 2)];
+    for &(progress, color, bar_y) in bars.iter() {
+        canvas.set_draw_color(color);
+        canvas.draw_rect(Rect::new(layout.hud_x, bar_y, width, height))
+            .expect("Couldn't draw the race bar");
+        let filled = (progress * width as f64) as u32;
+        if filled > 0 {
+            canvas.fill_rect(Rect::new(layout.hud_x, bar_y, filled, height))
+                .expect("Couldn't draw the race bar");
+        }
+    }
+    if let Some(split_text) = ghost.last_split_text() {
+        let split = create_texture_from_text(texture_creator, font, &split_text, theme.text_color)
+            .expect("Couldn't render the split text");
+        canvas.copy(&split, None, get_rect_from_text(font, &split_text, layout.hud_x, layout.hud_line_y(16), layout.text_height))
+            .expect("Couldn't copy the split text");
+    }
+}
+
+// The listeners of every game: the statistics, then the objective and the
+// achievements if any. The modes add the other ones they want before the game
+// starts.
+fn game_bus<'a>(stats: &'a RefCell<Stats>,
+                objective: Option<&'a RefCell<Objective>>,
+                achievements: Option<&'a RefCell<AchievementTracker<'a>>>) -> EventBus<'a> {
+    let mut bus = EventBus::new();
+    bus.subscribe(stats);
+    if let Some(objective) = objective {
+        bus.subscribe(objective);
+    }
+    if let Some(achievements) = achievements {
+        bus.subscribe(achievements);
+    }
+    bus
+}
+
+// Advances the game by one frame, applying the given inputs first, then hands
+// the events over to `bus`, which has `stats` and `objective` among its
+// listeners. Returns true if the objective has been completed.
+fn simulate_frame(tetris: &mut Tetris,
+                  inputs: &[Input],
+                  stats: &RefCell<Stats>,
+                  objective: Option<&RefCell<Objective>>,
+                  record: Option<&mut Replay>,
+                  bus: &EventBus) -> bool {
+    if let Some(objective) = objective {
+        objective.borrow_mut().tick();
+    }
+    events::simulate_frame(tetris, inputs, Some(stats), record, bus);
+    objective.is_some_and(|objective| objective.borrow().is_completed())
+}
+
+// Plays the game until it ends, `bus` being built by `game_bus` from `stats`,
+// `objective` and `achievements`. The achievements are only unlocked for the
+// keyboard players.
+fn run_game(tetris: &mut Tetris,
+            objective: Option<&RefCell<Objective>>,
+            stats: &RefCell<Stats>,
+            achievements: Option<&RefCell<AchievementTracker>>,
+            player: &mut Player,
+            mut record: Option<&mut Replay>,
+            mut history: Option<&mut History>,
+            mut ghost: Option<&mut Ghost>,
+            bus: &EventBus,
+            canvas: &mut Canvas<Window>,
+            event_pump: &mut EventPump,
+            texture_creator: &TextureCreator<WindowContext>,
+            theme: &Theme) -> GameEnd {
+    let mut layout = Layout::from_canvas(canvas, tetris.game_map.width(), tetris.game_map.len() - HIDDEN_LINES);
+    let mut inputs = Vec::new();
+    let mut rewinds = 0;
+    // the keys only control the game when a human plays, they are still read
+    // to quit
+    let controls = match *player {
+        Player::Keyboard(ref profile) => profile.controls.clone(),
+        _ => Controls::new(),
+    };
+    // names of the achievements just unlocked, with the frames left to show
+    // them
+    let mut toasts: Vec<(&'static str, u32)> = Vec::new();
+    let mut auto_shift = AutoShift::new();
+    let mut previous_time = Instant::now();
+    // frames left before the invisible stack hides again
+    let mut flash_frames: u32 = 0;
+    // time not simulated yet
+    let mut lag = Duration::new(0, 0);
+
+    loop {
+        let frame_start = Instant::now();
+        lag += frame_start - previous_time;
+        previous_time = frame_start;
+        if lag > MAX_LAG {
+            lag = MAX_LAG;
+        }
+
+        if let Some(quit) = handle_events(&mut inputs, &mut rewinds, &controls, event_pump, canvas, &mut layout) {
+            return quit
+        }
+        // only the practice games keep what is needed to take pieces back
+        if let Some(ref mut history) = history {
+            for _ in 0..rewinds {
+                history.rewind(tetris);
+            }
+        }
+        rewinds = 0;
+
+        // the inputs are applied at the next simulated frame, so the game
+        // plays the same whatever the display rate is
+        while lag >= FRAME_DURATION {
+            lag -= FRAME_DURATION;
+            let frame_inputs = match *player {
+                Player::Keyboard(_) => {
+                    let mut frame_inputs = std::mem::take(&mut inputs);
+                    auto_shift.update(event_pump, &controls, &mut frame_inputs);
+                    if tetris.current_piece.is_none() {
+                        add_held_inputs(event_pump, &controls, &mut frame_inputs);
+                    }
+                    if tetris.modifiers.mirror {
+                        frame_inputs = frame_inputs.into_iter().map(mirror_input).collect();
+                    }
+                    frame_inputs
+                },
+                Player::Bot(ref mut bot) => bot.next_input(tetris).into_iter().collect(),
+                Player::Replay(ref mut playback) => {
+                    if playback.is_finished() {
+                        return GameEnd::ReplayEnded
+                    }
+                    playback.next_frame()
+                },
+            };
+            // the keys only control the game when a human plays
+            inputs.clear();
+            let nb_lines = tetris.nb_lines;
+            let nb_pieces = stats.borrow().nb_pieces;
+            flash_frames = flash_frames.saturating_sub(1);
+            let completed = simulate_frame(tetris, &frame_inputs, stats, objective, record.as_deref_mut(), bus);
+            let stats = stats.borrow();
+            if tetris.nb_lines > nb_lines {
+                flash_frames = FLASH_FRAMES;
+            }
+            if stats.nb_pieces > nb_pieces {
+                if let Some(ref mut history) = history {
+                    history.record(tetris);
+                }
+            }
+            if let Some(ref mut ghost) = ghost {
+                if ghost.update(tetris, stats.frames) {
+                    println!("Split {}", ghost.last_split_text().unwrap_or_default());
+                }
+            }
+            for toast in toasts.iter_mut() {
+                toast.1 -= 1;
+            }
+            toasts.retain(|toast| toast.1 > 0);
+            if let (Player::Keyboard(ref mut profile), Some(achievements)) = (&mut *player, achievements) {
+                for achievement in achievements.borrow_mut().take_unlocked() {
+                    if profile.unlock(achievement.id) {
+                        println!("Achievement unlocked: {}", achievement.name);
+                        toasts.push((achievement.name, TOAST_FRAMES));
+                    }
+                }
+            }
+            if completed {
+                return GameEnd::Completed
+            }
+            if tetris.is_game_over() {
+                return GameEnd::ToppedOut
+            }
+        }
+
+        // the invisible stack shows for a moment when lines are cleared
+        let mut modifiers = tetris.modifiers;
+        modifiers.invisible &= flash_frames == 0;
+        draw_board(canvas, theme, &layout, &tetris.game_map, &modifiers);
+        if let Some(target) = tetris.trainer.as_ref().and_then(|trainer| trainer.target.as_ref()) {
+            draw_target(canvas, theme, &layout, &modifiers, target);
+        }
+
+        if let Some(ref piece) = tetris.current_piece {
+            // the piece is drawn between its line and the next one depending
+            // on the time elapsed since the last simulated frame
+            let frame_fraction = lag.subsec_nanos() as f32 / FRAME_DURATION.subsec_nanos() as f32;
+            let fall_offset = (tetris.fall_progress(frame_fraction) * layout.case_size as f32) as i32;
+            // the part of the piece still in the vanish zone is drawn above the grid
+            for (line_nb, line) in piece.states[piece.current_state as usize].iter().enumerate() {
+                for (case_nb, case) in line.iter().enumerate() {
+                    if *case == 0 {
+                        continue
+                    }
+                    draw_case(canvas, theme, &layout, &modifiers, *case, (piece.x + case_nb as isize) as i32,
+                              (piece.y + line_nb) as i32 - HIDDEN_LINES as i32, fall_offset);
+                }
+            }
+        }
+
+        let objective = objective.map(|objective| objective.borrow());
+        display_game_information(&tetris, objective.as_deref(), &stats.borrow(), canvas, texture_creator, theme,
+                                 &layout);
+        if let (Some(ghost), Some(objective)) = (ghost.as_deref(), objective.as_deref()) {
+            draw_race(canvas, texture_creator, theme, &layout, tetris, objective.goal, ghost);
+        }
+        // over the top of the grid
+        for (toast_nb, (name, _)) in toasts.iter().enumerate() {
+            let toast_text = format!("Achievement: {}", name);
+            let toast = create_texture_from_text(texture_creator, &theme.font, &toast_text, theme.highlight_color)
+                .expect("Couldn't render the achievement text");
+            canvas.copy(&toast, None, get_rect_from_text(&theme.font, &toast_text, layout.grid_x,
+                                                         layout.grid_y + toast_nb as i32 * layout.text_height as i32,
+                                                         layout.text_height))
+                .expect("Couldn't copy the achievement text");
+        }
+        canvas.present();
+
+        // without vsync, don't spin faster than needed
+        let frame_time = frame_start.elapsed();
+        if frame_time < MIN_FRAME_DURATION {
+            ::std::thread::sleep(MIN_FRAME_DURATION - frame_time);
+        }
+    }
+}
+
+// lines of the results screen
+fn game_results(tetris: &Tetris, stats: &Stats) -> Vec<String> {
+    let mut results = tetris.game_over.iter().map(|reason| reason.description().to_owned()).collect::<Vec<_>>();
+    results.extend(vec![format!("Score: {}", tetris.score),
+                        format!("Lines: {}", tetris.nb_lines),
+                        format!("Level: {}", tetris.current_level)]);
+    if !tetris.modifiers.is_none() {
+        results.push(format!("Modifiers: {}", tetris.modifiers.names().join(", ")));
+    }
+    if let Some(ref trainer) = tetris.trainer {
+        results.extend(trainer.summary_lines());
+    }
+    results.extend(stats.summary_lines());
+    results
+}
+
+// Plays a marathon game until it ends or the player quits, in which case
+// the game is saved with its statistics to be resumed later. The game is
+// recorded in `record` if given. Returns false if the window has been closed.
+fn play_marathon(tetris: &mut Tetris,
+                 stats: Stats,
+                 options: &Options,
+                 mut record: Option<Replay>,
+                 profile: &mut Profile,
+                 canvas: &mut Canvas<Window>,
+                 event_pump: &mut EventPump,
+                 texture_creator: &TextureCreator<WindowContext>,
+                 theme: &Theme) -> bool {
+    let stats = RefCell::new(stats);
+    // the bot games don't go to the highscores nor unlock achievements
+    let achievements = RefCell::new(AchievementTracker::new(tetris, &stats, false));
+    let tracker = if options.ai { None } else { Some(&achievements) };
+    let highscores = RefCell::new(HighscoreSubmission::new(&options.highscore_file));
+    let log = RefCell::new(EventLog);
+    let mut bus = game_bus(&stats, None, tracker);
+    if !options.ai {
+        bus.subscribe(&highscores);
+    }
+    if options.log_events {
+        bus.subscribe(&log);
+    }
+    let mut player = if options.ai { Player::Bot(Bot::new()) } else { Player::Keyboard(&mut *profile) };
+    let end = run_game(tetris, None, &stats, tracker, &mut player, record.as_mut(), None, None, &bus,
+                       canvas, event_pump, texture_creator, theme);
+    let stats = stats.borrow();
+    if let (Some(replay), Some(path)) = (record, options.record.as_ref()) {
+        match replay.save(path) {
+            Ok(()) => println!("Replay saved to {}", path),
+            Err(e) => println!("Couldn't save the replay: {}", e),
+        }
+    }
+    match end {
+        GameEnd::Quit | GameEnd::Closed => {
+            // the bot games aren't worth resuming
+            if !options.ai && !save::save_game(tetris, &stats) {
+                println!("Couldn't save the game");
+            }
+            !matches!(end, GameEnd::Closed)
+        },
+        GameEnd::ToppedOut | GameEnd::Completed | GameEnd::ReplayEnded => {
+            if options.ai {
+                println!("Bot game over: score {}, {} lines", tetris.score, tetris.nb_lines);
+            } else {
+                print_information(tetris, &stats, Some(&highscores.borrow()));
+                profile.lifetime.add_game("marathon", tetris.score, tetris.nb_lines);
+            }
+            show_results(canvas, event_pump, texture_creator, theme, "Game over", &game_results(tetris, &stats))
+        },
+    }
+}
+
+// Plays a marathon game in which the placements can be taken back, the game
+// not counting for the highscores once it has been done. Practice games
+// aren't saved. Returns false if the window has been closed.
+fn play_practice(tetris: &mut Tetris,
+                 options: &Options,
+                 profile: &mut Profile,
+                 canvas: &mut Canvas<Window>,
+                 event_pump: &mut EventPump,
+                 texture_creator: &TextureCreator<WindowContext>,
+                 theme: &Theme) -> bool {
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let achievements = RefCell::new(AchievementTracker::new(tetris, &stats, false));
+    // taking a placement back keeps the game from the highscores
+    let highscores = RefCell::new(HighscoreSubmission::new(&options.highscore_file));
+    let log = RefCell::new(EventLog);
+    let mut bus = game_bus(&stats, None, Some(&achievements));
+    bus.subscribe(&highscores);
+    if options.log_events {
+        bus.subscribe(&log);
+    }
+    let mut history = History::new(tetris);
+    let end = run_game(tetris, None, &stats, Some(&achievements), &mut Player::Keyboard(&mut *profile),
+                       None, Some(&mut history), None, &bus,
+                       canvas, event_pump, texture_creator, theme);
+    let stats = stats.borrow();
+    match end {
+        GameEnd::Closed => false,
+        GameEnd::Quit => true,
+        GameEnd::ToppedOut | GameEnd::Completed | GameEnd::ReplayEnded => {
+            let highscores = if history.nb_rewinds == 0 { Some(highscores.borrow()) } else { None };
+            print_information(tetris, &stats, highscores.as_deref());
+            profile.lifetime.add_game("practice", tetris.score, tetris.nb_lines);
+            let mut results = game_results(tetris, &stats);
+            if history.nb_rewinds > 0 {
+                println!("Placements taken back: {} (not counted for the highscores)", history.nb_rewinds);
+                results.push(format!("Placements taken back: {}", history.nb_rewinds));
+            }
+            show_results(canvas, event_pump, texture_creator, theme, "Game over", &results)
+        },
+    }
+}
+
+// Plays a sprint or an ultra game, racing the ghost of the best game of the
+// profile when `show_ghost` is set. The game is kept as the new best one when
+// it beats it. Returns false if the window has been closed.
+fn play_race(mode: Mode,
+             config: &GameConfig,
+             show_ghost: bool,
+             profile: &mut Profile,
+             canvas: &mut Canvas<Window>,
+             event_pump: &mut EventPump,
+             texture_creator: &TextureCreator<WindowContext>,
+             theme: &Theme) -> bool {
+    let (mode_name, goal) = match mode {
+        Mode::Sprint => ("sprint", Goal::ClearLines(SPRINT_LINES)),
+        _ => ("ultra", Goal::TimeLimit(ULTRA_SECS * FRAMES_PER_SECOND)),
+    };
+    if let Err(e) = config.check() {
+        println!("Couldn't start the game: {}", e);
+        return true
+    }
+    let path = profile.ghost_path(mode_name, &config.modifiers);
+    let path_text = path.to_string_lossy().into_owned();
+    // a best game played on another board can't be compared
+    let best = match Replay::load(&path_text) {
+        Ok(replay) => Some(replay),
+        Err(_) if !path.exists() => None,
+        Err(e) => {
+            println!("Couldn't load the best game: {}", e);
+            None
+        },
+    }.filter(|replay| replay.config.nb_columns == config.nb_columns && replay.config.nb_lines == config.nb_lines);
+    let best_result = best.as_ref().map(|replay| replay.verify());
+    let mut ghost = best.as_ref()
+                        .zip(best_result.as_ref())
+                        .filter(|_| show_ghost)
+                        .map(|(replay, verification)| Ghost::new(replay, verification));
+
+    let mut tetris = Tetris::from_config(config);
+    let objective = RefCell::new(Objective::new(goal));
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let sprint = mode == Mode::Sprint && config.modifiers.is_none();
+    let achievements = RefCell::new(AchievementTracker::new(&tetris, &stats, sprint));
+    let bus = game_bus(&stats, Some(&objective), Some(&achievements));
+    let mut record = Replay::new(config);
+    let end = run_game(&mut tetris, Some(&objective), &stats, Some(&achievements), &mut Player::Keyboard(&mut *profile),
+                       Some(&mut record), None, ghost.as_mut(), &bus, canvas, event_pump, texture_creator, theme);
+    let stats = stats.borrow();
+    let (title, new_best) = match (end, mode) {
+        (GameEnd::Completed, Mode::Sprint) => {
+            ("Sprint completed", best_result.as_ref().is_none_or(|best| record.nb_frames < best.nb_frames))
+        },
+        // the score counts in ultra even when topping out
+        (GameEnd::Completed, _) | (GameEnd::ToppedOut, Mode::Ultra) => {
+            ("Time is up", best_result.as_ref().is_none_or(|best| tetris.score > best.score))
+        },
+        (GameEnd::ToppedOut, _) => ("Game over", false),
+        (GameEnd::Closed, _) => return false,
+        (GameEnd::Quit, _) | (GameEnd::ReplayEnded, _) => return true,
+    };
+    print_information(&tetris, &stats, None);
+    profile.lifetime.add_game(mode_name, tetris.score, tetris.nb_lines);
+    let mut results = game_results(&tetris, &stats);
+    if let Some(ref ghost) = ghost {
+        results.extend(ghost.summary_lines());
+    }
+    if new_best {
+        let saved = match path.parent() {
+            Some(dir) => std::fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e)),
+            None => Ok(()),
+        }.and_then(|_| record.save(&path_text));
+        match saved {
+            Ok(()) => println!("New personal best, saved to {}", path_text),
+            Err(e) => println!("Couldn't save the personal best: {}", e),
+        }
+        results.push("New personal best".to_owned());
+    } else if let Some(best) = best_result {
+        results.push(match mode {
+            Mode::Sprint => format!("Personal best: {:.2} s", best.nb_frames as f64 / FRAMES_PER_SECOND as f64),
+            _ => format!("Personal best: {}", best.score),
+        });
+    }
+    show_results(canvas, event_pump, texture_creator, theme, title, &results)
 }
 
-fn is_time_over(tetris: &Tetris, timer: &SystemTime) -> bool{
-    match timer.elapsed() {
-        Ok(elapsed) => {
-            let millis = elapsed.as_millis();
-            millis > LEVEL_TIMES[tetris.current_level as usize] as u128
+// Plays a dig race, where the garbage has to be cleared as fast as possible.
+// Returns false if the window has been closed.
+fn play_dig(config: &GameConfig,
+            profile: &mut Profile,
+            canvas: &mut Canvas<Window>,
+            event_pump: &mut EventPump,
+            texture_creator: &TextureCreator<WindowContext>,
+            theme: &Theme) -> bool {
+    if let Err(e) = config.check() {
+        println!("Couldn't start the game: {}", e);
+        return true
+    }
+    let mut tetris = Tetris::from_config(config);
+    let objective = RefCell::new(Objective::new(Goal::ClearGarbage));
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let achievements = RefCell::new(AchievementTracker::new(&tetris, &stats, false));
+    let bus = game_bus(&stats, Some(&objective), Some(&achievements));
+    let end = run_game(&mut tetris, Some(&objective), &stats, Some(&achievements), &mut Player::Keyboard(&mut *profile),
+                       None, None, None, &bus,
+                       canvas, event_pump, texture_creator, theme);
+    let stats = stats.borrow();
+    let title = match end {
+        GameEnd::Completed => {
+            println!("Garbage cleared in {:.2} s", stats.elapsed_secs());
+            "Garbage cleared"
         },
-        Err(_) => false,
+        GameEnd::ToppedOut => "Game over",
+        GameEnd::Closed => return false,
+        GameEnd::Quit | GameEnd::ReplayEnded => return true,
+    };
+    profile.lifetime.add_game("dig", tetris.score, tetris.nb_lines);
+    show_results(canvas, event_pump, texture_creator, theme, title, &game_results(&tetris, &stats))
+}
+
+// Plays a finesse training session until the player quits, then shows the
+// accuracy. Returns false if the window has been closed.
+fn play_trainer(config: &GameConfig,
+                profile: &mut Profile,
+                canvas: &mut Canvas<Window>,
+                event_pump: &mut EventPump,
+                texture_creator: &TextureCreator<WindowContext>,
+                theme: &Theme) -> bool {
+    if let Err(e) = config.check() {
+        println!("Couldn't start the training: {}", e);
+        return true
+    }
+    let mut tetris = Tetris::from_config(config);
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let achievements = RefCell::new(AchievementTracker::new(&tetris, &stats, false));
+    let bus = game_bus(&stats, None, Some(&achievements));
+    let end = run_game(&mut tetris, None, &stats, Some(&achievements), &mut Player::Keyboard(&mut *profile),
+                       None, None, None, &bus,
+                       canvas, event_pump, texture_creator, theme);
+    let stats = stats.borrow();
+    let title = match end {
+        GameEnd::Closed => return false,
+        GameEnd::Quit | GameEnd::Completed | GameEnd::ReplayEnded => "Training over",
+        GameEnd::ToppedOut => "Game over",
+    };
+    profile.lifetime.add_game("trainer", tetris.score, tetris.nb_lines);
+    show_results(canvas, event_pump, texture_creator, theme, title, &game_results(&tetris, &stats))
+}
+
+// Plays the challenge of the day, its result going to the daily leaderboard
+// even when the game is quit. Returns false if the window has been closed.
+fn play_daily(challenge: &Challenge,
+              options: &Options,
+              one_attempt: bool,
+              profile: &mut Profile,
+              canvas: &mut Canvas<Window>,
+              event_pump: &mut EventPump,
+              texture_creator: &TextureCreator<WindowContext>,
+              theme: &Theme) -> bool {
+    let title = format!("Daily challenge {}", challenge.date);
+    let mut scores = DailyScores::load(&options.daily_score_file);
+    if one_attempt && scores.has_played(&challenge.date, &profile.name) {
+        println!("{} already played the daily challenge of {}", profile.name, challenge.date);
+        let mut lines = vec!["Already played today".to_owned()];
+        lines.extend(scores.leaderboard_lines(&challenge.date));
+        return show_results(canvas, event_pump, texture_creator, theme, &title, &lines)
+    }
+    let mut tetris = Tetris::from_config(&challenge.config());
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let achievements = RefCell::new(AchievementTracker::new(&tetris, &stats, false));
+    let bus = game_bus(&stats, None, Some(&achievements));
+    let end = run_game(&mut tetris, None, &stats, Some(&achievements), &mut Player::Keyboard(&mut *profile),
+                       None, None, None, &bus,
+                       canvas, event_pump, texture_creator, theme);
+    let stats = stats.borrow();
+    let rank = scores.add(&challenge.date, &profile.name, tetris.score, tetris.nb_lines);
+    if !scores.save(&options.daily_score_file) {
+        println!("Couldn't save the daily scores");
+    }
+    if let GameEnd::Closed = end {
+        return false
+    }
+    print_information(&tetris, &stats, None);
+    println!("Daily rank:      {}", rank);
+    profile.lifetime.add_game("daily", tetris.score, tetris.nb_lines);
+    let mut results = game_results(&tetris, &stats);
+    results.push(format!("Rank: {}", rank));
+    results.extend(scores.leaderboard_lines(&challenge.date));
+    show_results(canvas, event_pump, texture_creator, theme, &title, &results)
+}
+
+// Plays the replay in the window. Returns false if the window has been
+// closed.
+fn play_replay(replay: &Replay,
+               canvas: &mut Canvas<Window>,
+               event_pump: &mut EventPump,
+               texture_creator: &TextureCreator<WindowContext>,
+               theme: &Theme) -> bool {
+    let mut tetris = replay.create_game();
+    let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+    let bus = game_bus(&stats, None, None);
+    let mut player = Player::Replay(Playback::new(replay));
+    match run_game(&mut tetris, None, &stats, None, &mut player, None, None, None, &bus, canvas, event_pump, texture_creator, theme) {
+        GameEnd::Closed => false,
+        GameEnd::Quit => true,
+        _ => show_results(canvas, event_pump, texture_creator, theme, "End of the replay",
+                          &game_results(&tetris, &stats.borrow())),
+    }
+}
+
+// the position given on the command line, checked against the board size
+fn load_position(options: &Options) -> Result<Option<Position>, String> {
+    match options.position {
+        Some(ref source) => {
+            let position = Position::load(source)?;
+            position.create_game(&options.config)?;
+            Ok(Some(position))
+        },
+        None => Ok(None),
+    }
+}
+
+// a new marathon game, starting from the command line position if any
+fn create_marathon_game(config: &GameConfig, position: Option<&Position>) -> Result<Tetris, String> {
+    config.check()?;
+    match position {
+        Some(position) => position.create_game(config),
+        None => Ok(Tetris::from_config(config)),
+    }
+}
+
+// prints the position in both formats
+fn convert_position(source: &str) {
+    let position = match Position::load(source) {
+        Ok(position) => position,
+        Err(e) => {
+            println!("Couldn't load the position: {}", e);
+            return
+        },
+    };
+    print!("{}", position.to_text());
+    match position.to_fumen() {
+        Ok(fumen) => println!("{}", fumen),
+        Err(e) => println!("Couldn't convert the position to fumen: {}", e),
+    }
+}
+
+// prints the result of every game, without opening a window
+fn run_headless(options: &Options, position: Option<&Position>) {
+    if let Some(ref path) = options.replay {
+        let replay = match Replay::load(path) {
+            Ok(replay) => replay,
+            Err(e) => {
+                println!("Couldn't load the replay: {}", e);
+                return
+            },
+        };
+        let mut tetris = replay.create_game();
+        let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+        let log = RefCell::new(EventLog);
+        let mut bus = game_bus(&stats, None, None);
+        if options.log_events {
+            bus.subscribe(&log);
+        }
+        let mut playback = Playback::new(&replay);
+        while !playback.is_finished() && !tetris.is_game_over() {
+            let inputs = playback.next_frame();
+            simulate_frame(&mut tetris, &inputs, &stats, None, None, &bus);
+        }
+        println!("Score: {}, lines: {}, level: {}", tetris.score, tetris.nb_lines, tetris.current_level);
+        for line in stats.borrow().summary_lines() {
+            println!("{}", line);
+        }
+        return
+    }
+
+    let mut total_score = 0;
+    let mut total_lines = 0;
+    for game_nb in 0..options.nb_games {
+        let config = options.game_config(game_nb);
+        let mut tetris = match create_marathon_game(&config, position) {
+            Ok(tetris) => tetris,
+            Err(e) => {
+                println!("Couldn't start the game: {}", e);
+                return
+            },
+        };
+        let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+        let log = RefCell::new(EventLog);
+        let mut bus = game_bus(&stats, None, None);
+        if options.log_events {
+            bus.subscribe(&log);
+        }
+        let mut record = options.record.as_ref().map(|_| Replay::new(&config));
+        let mut bot = Bot::new();
+        while !tetris.is_game_over() && stats.borrow().nb_pieces < options.max_pieces {
+            let inputs = bot.next_input(&tetris).into_iter().collect::<Vec<_>>();
+            simulate_frame(&mut tetris, &inputs, &stats, None, record.as_mut(), &bus);
+        }
+        println!("Game {} (seed {}): score {}, {} lines, level {}, {} pieces, {}",
+                 game_nb + 1, config.seed, tetris.score, tetris.nb_lines, tetris.current_level, stats.borrow().nb_pieces,
+                 tetris.game_over.map_or("stopped", |reason| reason.description()));
+        total_score += tetris.score as u64;
+        total_lines += tetris.nb_lines as u64;
+        if let (Some(replay), Some(path)) = (record, options.record.as_ref()) {
+            if let Err(e) = replay.save(path) {
+                println!("Couldn't save the replay: {}", e);
+            }
+        }
+    }
+    if options.nb_games > 1 {
+        println!("Average: score {:.1}, {:.1} lines",
+                 total_score as f64 / options.nb_games as f64,
+                 total_lines as f64 / options.nb_games as f64);
+    }
+}
+
+// returns false if the window has been closed
+fn play_puzzles(pack: &mut PuzzlePack,
+                modifiers: &Modifiers,
+                profile: &mut Profile,
+                canvas: &mut Canvas<Window>,
+                event_pump: &mut EventPump,
+                texture_creator: &TextureCreator<WindowContext>,
+                theme: &Theme) -> bool {
+    let mut selected = 0;
+    loop {
+        let title = format!("{} ({}/{})", pack.name, pack.nb_solved(), pack.puzzles.len());
+        let entries = pack.puzzles.iter()
+                                  .enumerate()
+                                  .map(|(index, puzzle)| format!("{} {}",
+                                                                 if pack.is_solved(index) { "[x]" } else { "[ ]" },
+                                                                 puzzle.name))
+                                  .collect::<Vec<_>>();
+        selected = match select_from_menu(canvas, event_pump, texture_creator, theme, &title, &entries, selected) {
+            MenuAction::Selected(index) => index,
+            MenuAction::Back => return true,
+            MenuAction::Quit => return false,
+        };
+
+        let puzzle = &pack.puzzles[selected];
+        let mut tetris = puzzle.create_game();
+        // the puzzles are made for their board, big mode doesn't apply to them
+        tetris.modifiers = Modifiers { big: false, ..*modifiers };
+        let objective = RefCell::new(Objective::new(puzzle.goal));
+        println!("Puzzle \"{}\": {} with {}", puzzle.name, puzzle.goal.description(), puzzle.pieces_text());
+        let stats = RefCell::new(Stats::new(tetris.game_map.width()));
+        let achievements = RefCell::new(AchievementTracker::new(&tetris, &stats, false));
+        let bus = game_bus(&stats, Some(&objective), Some(&achievements));
+        match run_game(&mut tetris, Some(&objective), &stats, Some(&achievements), &mut Player::Keyboard(&mut *profile),
+                       None, None, None, &bus,
+                       canvas, event_pump, texture_creator, theme) {
+            GameEnd::Completed => {
+                println!("Puzzle solved!");
+                pack.mark_solved(selected);
+                if selected + 1 < pack.puzzles.len() {
+                    selected += 1;
+                }
+            },
+            GameEnd::Closed => return false,
+            GameEnd::Quit | GameEnd::ReplayEnded => {},
+            GameEnd::ToppedOut => {
+                println!("Puzzle failed: {}", tetris.game_over.map_or("", |reason| reason.description()));
+            },
+        }
+    }
+}
+
+// falls back to the classic theme if the wanted one can't be loaded
+fn load_theme_or_default<'a, 'ttf>(name: &str,
+                                   canvas: &mut Canvas<Window>,
+                                   texture_creator: &'a TextureCreator<WindowContext>,
+                                   ttf_context: &'ttf Sdl2TtfContext,
+                                   options: &DisplayOptions) -> Theme<'a, 'ttf> {
+    match load_theme(&Path::new(THEMES_DIR).join(name), canvas, texture_creator, ttf_context, options) {
+        Ok(theme) => theme,
+        Err(e) => {
+            println!("Couldn't load the theme: {}", e);
+            load_theme(&Path::new(THEMES_DIR).join("classic"), canvas, texture_creator, ttf_context, options)
+                .expect("Couldn't load the classic theme")
+        },
+    }
+}
+
+// toggles the modifiers of the next games, returns false if the window has
+// been closed
+fn choose_modifiers(modifiers: &mut Modifiers,
+                    canvas: &mut Canvas<Window>,
+                    event_pump: &mut EventPump,
+                    texture_creator: &TextureCreator<WindowContext>,
+                    theme: &Theme) -> bool {
+    let mut selected = 0;
+    loop {
+        let entries = MODIFIER_NAMES.iter()
+                                    .map(|name| format!("{}: {}", name, on_off(modifiers.is_set(name))))
+                                    .collect::<Vec<_>>();
+        selected = match select_from_menu(canvas, event_pump, texture_creator, theme, "Modifiers", &entries, selected) {
+            MenuAction::Selected(index) => index,
+            MenuAction::Back => return true,
+            MenuAction::Quit => return false,
+        };
+        modifiers.toggle(MODIFIER_NAMES[selected]);
+    }
+}
+
+// returns false if the window has been closed
+fn show_options<'a, 'ttf>(theme: &mut Theme<'a, 'ttf>,
+                          settings: &mut Settings,
+                          profile: &mut Profile,
+                          canvas: &mut Canvas<Window>,
+                          event_pump: &mut EventPump,
+                          texture_creator: &'a TextureCreator<WindowContext>,
+                          ttf_context: &'ttf Sdl2TtfContext) -> bool {
+    let mut selected = 0;
+    loop {
+        let entries = [format!("Theme: {}", theme.name),
+                       format!("Palette: {}", settings.display.palette.name()),
+                       format!("Patterns: {}", on_off(settings.display.patterns)),
+                       format!("High contrast: {}", on_off(settings.display.high_contrast)),
+                       format!("DAS: {} frames", profile.controls.das_frames),
+                       format!("ARR: {} frames", profile.controls.arr_frames),
+                       "Key bindings".to_owned(),
+                       format!("One daily attempt: {}", on_off(settings.daily_one_attempt)),
+                       format!("Ghost race: {}", on_off(settings.ghost))];
+        selected = match select_from_menu(canvas, event_pump, texture_creator, theme, "Options", &entries, selected) {
+            MenuAction::Selected(index) => index,
+            MenuAction::Back => return true,
+            MenuAction::Quit => return false,
+        };
+
+        // neither the profile settings nor the game ones change the theme
+        match selected {
+            4 => {
+                profile.controls.das_frames = if profile.controls.das_frames >= MAX_DAS_FRAMES {
+                    MIN_DAS_FRAMES
+                } else {
+                    profile.controls.das_frames + 1
+                };
+            },
+            5 => profile.controls.arr_frames = (profile.controls.arr_frames + 1) % (MAX_ARR_FRAMES + 1),
+            6 => {
+                if !choose_bindings(&mut profile.controls, canvas, event_pump, texture_creator, theme) {
+                    return false
+                }
+            },
+            7 | 8 => {
+                if selected == 7 {
+                    settings.daily_one_attempt = !settings.daily_one_attempt;
+                } else {
+                    settings.ghost = !settings.ghost;
+                }
+                if !settings.save() {
+                    println!("Couldn't save the settings");
+                }
+            },
+            _ => {},
+        }
+        if selected >= 4 {
+            continue
+        }
+
+        let mut theme_dir = theme.dir.clone();
+        match selected {
+            0 => {
+                // cycle through the available themes
+                let themes = list_themes();
+                let next = match themes.iter().position(|dir| *dir == theme.dir) {
+                    Some(index) => (index + 1) % themes.len(),
+                    None => 0,
+                };
+                if let Some(dir) = themes.get(next) {
+                    theme_dir = dir.clone();
+                }
+            },
+            1 => {
+                let current = Palette::ALL.iter()
+                                          .position(|palette| *palette == settings.display.palette)
+                                          .unwrap_or(0);
+                settings.display.palette = Palette::ALL[(current + 1) % Palette::ALL.len()];
+            },
+            2 => settings.display.patterns = !settings.display.patterns,
+            _ => settings.display.high_contrast = !settings.display.high_contrast,
+        }
+
+        match load_theme(&theme_dir, canvas, texture_creator, ttf_context, &settings.display) {
+            Ok(new_theme) => {
+                settings.theme = theme_dir.file_name()
+                                          .map(|name| name.to_string_lossy().into_owned())
+                                          .unwrap_or_default();
+                profile.theme = settings.theme.clone();
+                *theme = new_theme;
+            },
+            Err(e) => println!("Couldn't load the theme: {}", e),
+        }
+        if !settings.save() {
+            println!("Couldn't save the settings");
+        }
+    }
+}
+
+// Lets the player bind a key to each action. Returns false if the window has
+// been closed.
+fn choose_bindings(controls: &mut Controls,
+                   canvas: &mut Canvas<Window>,
+                   event_pump: &mut EventPump,
+                   texture_creator: &TextureCreator<WindowContext>,
+                   theme: &Theme) -> bool {
+    let mut selected = 0;
+    loop {
+        let entries = ACTION_NAMES.iter()
+                                  .map(|(action, name)| {
+                                      format!("{}: {}", name, controls.key(*action).map(|key| key.name()).unwrap_or_default())
+                                  })
+                                  .collect::<Vec<_>>();
+        selected = match select_from_menu(canvas, event_pump, texture_creator, theme, "Key bindings", &entries, selected) {
+            MenuAction::Selected(index) => index,
+            MenuAction::Back => return true,
+            MenuAction::Quit => return false,
+        };
+        let (action, name) = ACTION_NAMES[selected];
+        match read_key(canvas, event_pump, texture_creator, theme, &format!("Key for {}", name)) {
+            Answer::Given(keycode) => controls.bind(action, keycode),
+            Answer::Back => {},
+            Answer::Quit => return false,
+        }
+    }
+}
+
+// Lets the player pick a profile, create one or look at the statistics and
+// achievements of the current one. Returns false if the window has been closed.
+fn choose_profile(profiles: &mut Profiles,
+                  default_theme: &str,
+                  canvas: &mut Canvas<Window>,
+                  event_pump: &mut EventPump,
+                  texture_creator: &TextureCreator<WindowContext>,
+                  theme: &Theme) -> bool {
+    let mut entries = profiles.profiles.iter().map(|profile| profile.name.clone()).collect::<Vec<_>>();
+    let nb_profiles = entries.len();
+    entries.push("New profile".to_owned());
+    entries.push("Statistics".to_owned());
+    entries.push("Achievements".to_owned());
+    loop {
+        let selected = match select_from_menu(canvas, event_pump, texture_creator, theme, "Profiles", &entries, profiles.selected) {
+            MenuAction::Selected(index) => index,
+            MenuAction::Back => return true,
+            MenuAction::Quit => return false,
+        };
+        if selected < nb_profiles {
+            profiles.selected = selected;
+            return true
+        }
+        if selected == nb_profiles {
+            match read_text(canvas, event_pump, texture_creator, theme, "Profile name", MAX_NAME_LEN) {
+                Answer::Given(name) => {
+                    if profiles.add(&name, default_theme) {
+                        return true
+                    }
+                    println!("There is already a profile named \"{}\"", name);
+                },
+                Answer::Back => {},
+                Answer::Quit => return false,
+            }
+            continue
+        }
+        let profile = profiles.current();
+        let (title, lines) = if selected == nb_profiles + 1 {
+            (profile.name.clone(), profile.lifetime.summary_lines())
+        } else {
+            let lines = ACHIEVEMENTS.iter()
+                                    .map(|achievement| {
+                                        match profile.achievements.iter().find(|(id, _)| id == achievement.id) {
+                                            Some((_, timestamp)) => format!("[x] {}: {} ({})", achievement.name,
+                                                                            achievement.description, format_date(*timestamp)),
+                                            None => format!("[ ] {}: {}", achievement.name, achievement.description),
+                                        }
+                                    })
+                                    .collect();
+            (format!("Achievements of {}", profile.name), lines)
+        };
+        if !show_results(canvas, event_pump, texture_creator, theme, &title, &lines) {
+            return false
+        }
+    }
+}
+
+// called after every game, so that its statistics and achievements aren't
+// lost if the program doesn't exit normally
+fn save_profiles(profiles: &Profiles) {
+    if let Err(e) = profiles.save() {
+        println!("Couldn't save the profiles: {}", e);
     }
 }
 
 fn main() {
+    let options = match Options::parse(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            println!("{}", e);
+            cli::print_usage();
+            return
+        },
+    };
+    if options.help {
+        cli::print_usage();
+        return
+    }
+    if let (Some(path), Some(replay_path)) = (options.export.as_ref(), options.replay.as_ref()) {
+        let result = Replay::load(replay_path)
+            .and_then(|replay| export::export_replay(&replay, path, options.export_fps, options.window_size));
+        if let Err(e) = result {
+            println!("Couldn't export the replay: {}", e);
+        }
+        return
+    }
+    if let Some(ref source) = options.convert {
+        convert_position(source);
+        return
+    }
+    let position = match load_position(&options) {
+        Ok(position) => position,
+        Err(e) => {
+            println!("Couldn't load the position: {}", e);
+            return
+        },
+    };
+    if options.headless {
+        run_headless(&options, position.as_ref());
+        return
+    }
+
     let sdl_context = sdl2::init().expect("SDL initialization failed");
 
     let video_subsystem = sdl_context
         .video()
         .expect("Couldn't get SDL video subsystem");
 
-    let width = 800;
-    let height = 800;
+    let (width, height) = options.window_size;
 
     let mut event_pump = sdl_context
         .event_pump()
         .expect("Failed to get SDL event pump");
 
-    let mut tetris = Tetris::new();
-    let mut timer = SystemTime::now();
-
-    let grid_x = 20; //(width - TETRIS_HEIGHT as u32 * 10) as i32 / 2;
-    let grid_y = (height - TETRIS_HEIGHT as u32 * 16) as i32 / 2;
-
-    let window = video_subsystem
-        .window("Tetris", width, height)
-        .position_centered()
-        .build()
+    let mut window_builder = video_subsystem.window("Tetris", width, height);
+    window_builder.position_centered().resizable();
+    if options.fullscreen {
+        window_builder.fullscreen_desktop();
+    }
+    let mut window = window_builder.build()
         .expect("Failed to create the window");
+    window.set_minimum_size(320, 320)
+        .expect("Couldn't set the window minimum size");
 
     let mut canvas = window
         .into_canvas()
@@ -259,120 +1467,202 @@ fn main() {
 
     let texture_creator: TextureCreator<_> = canvas.texture_creator();
 
-    let grid = create_texture_rect(&mut canvas, &texture_creator, Color::RGB(0, 0, 0)
-                                   , TETRIS_HEIGHT as u32 * 10, TETRIS_HEIGHT as u32 * 16)
-        .expect("Failed to create the grid texture");
+    let _image_context = sdl2::image::init(INIT_PNG | INIT_JPG).expect("SDL image initialization failed");
+    let ttf_context = sdl2::ttf::init().expect("SDL TTF initialization failed");
 
-    let border = create_texture_rect(&mut canvas, &texture_creator, Color::RGB(255, 255, 255)
-                                    , TETRIS_HEIGHT as u32 * 10 + 20, TETRIS_HEIGHT as u32 * 16 + 20)
-        .expect("Failed to create the border texture");
+    let mut settings = Settings::load();
+    // the theme from the settings goes to the new profiles
+    let mut profiles = Profiles::load(&settings.theme).unwrap_or_else(|e| {
+        println!("Couldn't load the profiles: {}", e);
+        Profiles::new(&settings.theme)
+    });
+    let theme_name = options.theme.clone().unwrap_or_else(|| profiles.current().theme.clone());
+    let mut theme = load_theme_or_default(&theme_name, &mut canvas, &texture_creator, &ttf_context, &settings.display);
 
-    macro_rules! texture {
-        ($r:expr, $g: expr, $b: expr) => {
-            create_texture_rect(&mut canvas, &texture_creator, Color::RGB($r, $g, $b)
-                               , TETRIS_HEIGHT as u32, TETRIS_HEIGHT as u32)
-                .unwrap()
+    if let Some(ref path) = options.replay {
+        match Replay::load(path) {
+            Ok(replay) => {
+                play_replay(&replay, &mut canvas, &mut event_pump, &texture_creator, &theme);
+            },
+            Err(e) => println!("Couldn't load the replay: {}", e),
         }
+        return
     }
 
-    let textures = [texture!(255,  69,  69),
-                          texture!(255, 220,  69),
-                          texture!(237, 150,  37),
-                          texture!(171,  99, 137),
-                          texture!( 77, 149, 239),
-                          texture!( 39, 218, 225),
-                          texture!( 45, 216,  47)];
-
-    let ttf_context = sdl2::ttf::init().expect("SDL TTF initialization failed");
-    let font = ttf_context
-        .load_font("assets/lucon.ttf", 64)
-        .expect("Couldn't load the font");
-
-
-
+    let mut selected = 0;
+    let mut nb_games = 0;
+    let mut modifiers = options.config.modifiers;
     loop {
-        if is_time_over(&tetris, &timer){
-            let mut make_permanent = false;
-            if let Some(ref mut piece) = tetris.current_piece {
-                let x = piece.x;
-                let y = piece.y + 1;
-                make_permanent = !piece.change_position(&tetris.game_map, x, y);
-            }
-            if make_permanent {
-                tetris.make_permanent();
-            }
-            timer = SystemTime::now();
+        let mut entries = Vec::new();
+        if save::has_saved_game() {
+            entries.push(("Continue", MainEntry::Continue));
         }
+        let profile_label = format!("Profile: {}", profiles.current().name);
+        entries.push((profile_label.as_str(), MainEntry::Profile));
+        entries.push(("Marathon", MainEntry::Marathon));
+        entries.push(("Practice", MainEntry::Practice));
+        entries.push(("Sprint", MainEntry::Sprint));
+        entries.push(("Ultra", MainEntry::Ultra));
+        entries.push(("Puzzles", MainEntry::Puzzles));
+        entries.push(("Dig race", MainEntry::Dig));
+        entries.push(("Finesse trainer", MainEntry::Trainer));
+        entries.push(("Daily challenge", MainEntry::Daily));
+        entries.push(("Sandbox", MainEntry::Sandbox));
+        entries.push(("Modifiers", MainEntry::Modifiers));
+        entries.push(("Options", MainEntry::Options));
+        entries.push(("Quit", MainEntry::Quit));
+        let labels = entries.iter().map(|entry| entry.0.to_owned()).collect::<Vec<_>>();
+        // the mode given on the command line skips the menu
+        let entry = match options.mode {
+            Some(Mode::Marathon) => MainEntry::Marathon,
+            Some(Mode::Practice) => MainEntry::Practice,
+            Some(Mode::Sprint) => MainEntry::Sprint,
+            Some(Mode::Ultra) => MainEntry::Ultra,
+            Some(Mode::Puzzles) => MainEntry::Puzzles,
+            Some(Mode::Dig) => MainEntry::Dig,
+            Some(Mode::Trainer) => MainEntry::Trainer,
+            Some(Mode::Daily) => MainEntry::Daily,
+            None => {
+                selected = match select_from_menu(&mut canvas, &mut event_pump, &texture_creator, &theme, "Tetris", &labels, selected) {
+                    MenuAction::Selected(index) => index,
+                    MenuAction::Back | MenuAction::Quit => break,
+                };
+                entries[selected].1
+            },
+        };
 
-        // draw the tetris grid here
-        canvas.set_draw_color(Color::RGB(0, 0, 255));
-        canvas.clear();
-        canvas.copy(&border, None,
-                    Rect::new(grid_x - 10,
-                              grid_y - 10,
-                              TETRIS_HEIGHT as u32 * 10 + 20,
-                              TETRIS_HEIGHT as u32 * 16 + 20))
-            .expect("Couldn't copy border texture into window");
-
-        canvas.copy(&grid, None,
-                    Rect::new(grid_x,
-                              grid_y,
-                              TETRIS_HEIGHT as u32 * 10,
-                              TETRIS_HEIGHT as u32 * 16 ))
-            .expect("Couldn't copy grid texture into window");
-
-
-
-        if tetris.current_piece.is_none() {
-            let current_piece = create_new_tetrimino();
-            if !current_piece.test_current_position(&tetris.game_map) {
-                print_information(&tetris);
-                break
-            }
-            tetris.current_piece = Some(current_piece);
-        }
-
-        let mut quit = false;
-        if !handle_events(&mut tetris, &mut quit, &mut timer, &mut event_pump) {
-            if let Some(ref mut piece) = tetris.current_piece {
-                //draw the current tetrimino here
-                for (line_nb, line) in piece.states[piece.current_state as usize].iter().enumerate() {
-                    for (case_nb, case) in line.iter().enumerate() {
-                        if *case == 0 {
-                            continue
+        match entry {
+            MainEntry::Continue => {
+                match save::load_game() {
+                    Ok((mut tetris, stats)) => {
+                        // a resumed game can't be replayed from its start
+                        if !play_marathon(&mut tetris, stats, &options, None, profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                            break
                         }
-                        canvas.copy(&textures[*case as usize - 1], None,
-                                    Rect::new(grid_x + (piece.x + case_nb as isize) as i32 * TETRIS_HEIGHT as i32,
-                                                    grid_y + (piece.y + line_nb) as i32 * TETRIS_HEIGHT as i32,
-                                                    TETRIS_HEIGHT as u32,
-                                                    TETRIS_HEIGHT as u32))
-                            .expect("Couldn't copy the current piece texture into canvas");
-                    }
+                    },
+                    Err(e) => println!("Couldn't load the saved game: {}", e),
                 }
-            }
+                // the entry is gone now
+                selected = 0;
+            },
+            MainEntry::Profile => {
+                let previous_theme = profiles.current().theme.clone();
+                if !choose_profile(&mut profiles, &settings.theme, &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                    break
+                }
+                if options.theme.is_none() && profiles.current().theme != previous_theme {
+                    theme = load_theme_or_default(&profiles.current().theme, &mut canvas, &texture_creator, &ttf_context,
+                                                  &settings.display);
+                }
+            },
+            MainEntry::Marathon => {
+                let mut config = options.game_config(nb_games);
+                config.modifiers = modifiers;
+                nb_games += 1;
+                match create_marathon_game(&config, position.as_ref()) {
+                    Ok(mut tetris) => {
+                        let record = options.record.as_ref().map(|_| Replay::new(&config));
+                        let stats = Stats::new(tetris.game_map.width());
+                        if !play_marathon(&mut tetris, stats, &options, record, profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                            break
+                        }
+                    },
+                    Err(e) => println!("Couldn't start the game: {}", e),
+                }
+            },
+            MainEntry::Practice => {
+                let mut config = options.game_config(nb_games);
+                config.modifiers = modifiers;
+                nb_games += 1;
+                match create_marathon_game(&config, position.as_ref()) {
+                    Ok(mut tetris) => {
+                        if !play_practice(&mut tetris, &options, profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                            break
+                        }
+                    },
+                    Err(e) => println!("Couldn't start the game: {}", e),
+                }
+            },
+            MainEntry::Sprint | MainEntry::Ultra => {
+                let mut config = options.game_config(nb_games);
+                config.modifiers = modifiers;
+                nb_games += 1;
+                let mode = if entry == MainEntry::Sprint { Mode::Sprint } else { Mode::Ultra };
+                if !play_race(mode, &config, settings.ghost, profiles.current_mut(), &mut canvas, &mut event_pump,
+                              &texture_creator, &theme) {
+                    break
+                }
+            },
+            MainEntry::Puzzles => {
+                let mut packs = puzzle::load_packs();
+                let mut selected_pack = 0;
+                let mut closed = false;
+                while !closed {
+                    let pack_entries = packs.iter()
+                                            .map(|pack| format!("{} ({}/{})", pack.name, pack.nb_solved(), pack.puzzles.len()))
+                                            .collect::<Vec<_>>();
+                    selected_pack = match select_from_menu(&mut canvas, &mut event_pump, &texture_creator, &theme,
+                                                           "Puzzle packs", &pack_entries, selected_pack) {
+                        MenuAction::Selected(index) => index,
+                        MenuAction::Back => break,
+                        MenuAction::Quit => {
+                            closed = true;
+                            break
+                        },
+                    };
+                    closed = !play_puzzles(&mut packs[selected_pack], &modifiers, profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &theme);
+                }
+                if closed {
+                    break
+                }
+            },
+            MainEntry::Dig => {
+                let mut config = options.dig_config(nb_games);
+                config.modifiers = modifiers;
+                nb_games += 1;
+                if !play_dig(&config, profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                    break
+                }
+            },
+            MainEntry::Trainer => {
+                let mut config = options.trainer_config(nb_games);
+                config.modifiers = modifiers;
+                nb_games += 1;
+                if !play_trainer(&config, profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                    break
+                }
+            },
+            MainEntry::Daily => {
+                // the date is read again, the game may have been left open
+                // overnight
+                let challenge = Challenge::today();
+                if !play_daily(&challenge, &options, settings.daily_one_attempt, profiles.current_mut(), &mut canvas,
+                               &mut event_pump, &texture_creator, &theme) {
+                    break
+                }
+            },
+            MainEntry::Sandbox => {
+                if !editor::run_sandbox(profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                    break
+                }
+            },
+            MainEntry::Modifiers => {
+                if !choose_modifiers(&mut modifiers, &mut canvas, &mut event_pump, &texture_creator, &theme) {
+                    break
+                }
+            },
+            MainEntry::Options => {
+                if !show_options(&mut theme, &mut settings, profiles.current_mut(), &mut canvas, &mut event_pump, &texture_creator, &ttf_context) {
+                    break
+                }
+            },
+            MainEntry::Quit => break,
         }
-
-        if quit {
-            print_information(&tetris);
+        save_profiles(&profiles);
+        if options.mode.is_some() {
             break
         }
-
-        // draw the game map here
-        for (line_nb, line) in tetris.game_map.iter().enumerate(){
-            for (case_nb, case) in line.iter().enumerate() {
-                if *case == 0 {
-                    continue
-                }
-                canvas.copy(&textures[*case as usize - 1], None,
-                            Rect::new(grid_x + case_nb as i32 * TETRIS_HEIGHT as i32,
-                                            grid_y + line_nb as i32 * TETRIS_HEIGHT as i32,
-                                            TETRIS_HEIGHT as u32,
-                                            TETRIS_HEIGHT as u32 ))
-                    .expect("Couldn't copy the placed piece texture into canvas");
-            }
-        }
-        display_game_information(&tetris, &mut canvas, &texture_creator, &font, width as i32 - grid_x - 300);
-        canvas.present();
-        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
     }
+    // the games ended by closing the window haven't been saved yet
+    save_profiles(&profiles);
 }
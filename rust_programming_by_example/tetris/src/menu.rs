@@ -0,0 +1,253 @@
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::render::{Canvas, TextureCreator};
+use sdl2::video::{Window, WindowContext};
+use sdl2::EventPump;
+
+use std::time::Duration;
+
+use crate::{create_texture_from_text, get_rect_from_text};
+use crate::layout::handle_window_event;
+use crate::theme::Theme;
+
+pub enum MenuAction {
+    Selected(usize),
+    Back,
+    Quit,
+}
+
+// how many lines of `pitch` pixels fit between `top` and the bottom margin
+// of a window `height` pixels high
+fn nb_visible_lines(height: u32, top: i32, pitch: i32) -> usize {
+    let margin = height as i32 / 20;
+    ((height as i32 - top - margin) / pitch).max(1) as usize
+}
+
+// Shows a vertical list of entries and blocks until the player picks one
+// (Return), goes back (Escape) or closes the window. The list scrolls to keep
+// the selected entry in the window.
+pub fn select_from_menu(canvas: &mut Canvas<Window>,
+                        event_pump: &mut EventPump,
+                        texture_creator: &TextureCreator<WindowContext>,
+                        theme: &Theme,
+                        title: &str,
+                        entries: &[String],
+                        start: usize) -> MenuAction {
+    let mut selected = if start < entries.len() { start } else { 0 };
+    // first entry shown
+    let mut first = 0;
+
+    loop {
+        for event in event_pump.poll_iter() {
+            if handle_window_event(&event, canvas) {
+                continue
+            }
+            match event {
+                Event::Quit {..} => return MenuAction::Quit,
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => return MenuAction::Back,
+                Event::KeyDown {keycode: Some(Keycode::Return), ..} => {
+                    if !entries.is_empty() {
+                        return MenuAction::Selected(selected);
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::Up), ..} => {
+                    if selected > 0 {
+                        selected -= 1;
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::Down), ..} => {
+                    if selected + 1 < entries.len() {
+                        selected += 1;
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        let (_, height) = canvas.output_size().expect("Couldn't get the window size");
+        let margin = height as i32 / 20;
+        let text_height = height / 20;
+        let pitch = text_height as i32 * 4 / 3;
+        let nb_visible = nb_visible_lines(height, margin * 3, pitch);
+        if selected < first {
+            first = selected;
+        } else if selected >= first + nb_visible {
+            first = selected + 1 - nb_visible;
+        }
+
+        let font = &theme.font;
+        theme.draw_background(canvas);
+
+        let title_texture = create_texture_from_text(texture_creator, font, title, theme.text_color)
+            .expect("Couldn't render the menu title");
+        canvas.copy(&title_texture, None, get_rect_from_text(font, title, margin, margin, text_height * 3 / 2))
+            .expect("Couldn't copy the menu title");
+
+        for (row, (entry_nb, entry)) in entries.iter().enumerate().skip(first).take(nb_visible).enumerate() {
+            let (text, color) = if entry_nb == selected {
+                (format!("> {}", entry), theme.highlight_color)
+            } else {
+                (format!("  {}", entry), theme.text_color)
+            };
+            let texture = create_texture_from_text(texture_creator, font, &text, color)
+                .expect("Couldn't render a menu entry");
+            canvas.copy(&texture, None, get_rect_from_text(font, &text, margin,
+                                                          margin * 3 + row as i32 * pitch,
+                                                          text_height))
+                .expect("Couldn't copy a menu entry");
+        }
+
+        canvas.present();
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+// what the player gave when asked for something
+pub enum Answer<T> {
+    Given(T),
+    Back,
+    Quit,
+}
+
+fn draw_prompt(canvas: &mut Canvas<Window>,
+               texture_creator: &TextureCreator<WindowContext>,
+               theme: &Theme,
+               title: &str,
+               line: &str) {
+    let (_, height) = canvas.output_size().expect("Couldn't get the window size");
+    let margin = height as i32 / 20;
+    let text_height = height / 20;
+    let font = &theme.font;
+    theme.draw_background(canvas);
+
+    let title_texture = create_texture_from_text(texture_creator, font, title, theme.text_color)
+        .expect("Couldn't render the prompt title");
+    canvas.copy(&title_texture, None, get_rect_from_text(font, title, margin, margin, text_height * 3 / 2))
+        .expect("Couldn't copy the prompt title");
+    let texture = create_texture_from_text(texture_creator, font, line, theme.highlight_color)
+        .expect("Couldn't render the prompt line");
+    canvas.copy(&texture, None, get_rect_from_text(font, line, margin, margin * 3, text_height))
+        .expect("Couldn't copy the prompt line");
+    canvas.present();
+}
+
+// Lets the player type a line of text, up to `max_len` characters, until
+// Return is pressed.
+pub fn read_text(canvas: &mut Canvas<Window>,
+                 event_pump: &mut EventPump,
+                 texture_creator: &TextureCreator<WindowContext>,
+                 theme: &Theme,
+                 title: &str,
+                 max_len: usize) -> Answer<String> {
+    let mut text = String::new();
+    loop {
+        for event in event_pump.poll_iter() {
+            if handle_window_event(&event, canvas) {
+                continue
+            }
+            match event {
+                Event::Quit {..} => return Answer::Quit,
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => return Answer::Back,
+                Event::KeyDown {keycode: Some(Keycode::Return), ..} => {
+                    if !text.trim().is_empty() {
+                        return Answer::Given(text.trim().to_owned());
+                    }
+                },
+                Event::KeyDown {keycode: Some(Keycode::Backspace), ..} => {
+                    text.pop();
+                },
+                Event::TextInput {text: typed, ..} => {
+                    for c in typed.chars().filter(|c| !c.is_control()) {
+                        if text.chars().count() < max_len {
+                            text.push(c);
+                        }
+                    }
+                },
+                _ => {}
+            }
+        }
+
+        // the cursor also keeps the line from being empty
+        draw_prompt(canvas, texture_creator, theme, title, &format!("{}_", text));
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+// Waits for a key press, Escape going back.
+pub fn read_key(canvas: &mut Canvas<Window>,
+                event_pump: &mut EventPump,
+                texture_creator: &TextureCreator<WindowContext>,
+                theme: &Theme,
+                title: &str) -> Answer<Keycode> {
+    loop {
+        for event in event_pump.poll_iter() {
+            if handle_window_event(&event, canvas) {
+                continue
+            }
+            match event {
+                Event::Quit {..} => return Answer::Quit,
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => return Answer::Back,
+                Event::KeyDown {keycode: Some(keycode), repeat: false, ..} => return Answer::Given(keycode),
+                _ => {}
+            }
+        }
+
+        draw_prompt(canvas, texture_creator, theme, title, "Press a key (Escape to cancel)");
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
+
+// Shows some lines of text until the player presses Return or Escape, the
+// arrows scrolling through the lines not fitting in the window. Returns false
+// if the window has been closed.
+pub fn show_results(canvas: &mut Canvas<Window>,
+                    event_pump: &mut EventPump,
+                    texture_creator: &TextureCreator<WindowContext>,
+                    theme: &Theme,
+                    title: &str,
+                    lines: &[String]) -> bool {
+    // first line shown
+    let mut first: usize = 0;
+    loop {
+        for event in event_pump.poll_iter() {
+            if handle_window_event(&event, canvas) {
+                continue
+            }
+            match event {
+                Event::Quit {..} => return false,
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} |
+                Event::KeyDown {keycode: Some(Keycode::Return), ..} => return true,
+                Event::KeyDown {keycode: Some(Keycode::Up), ..} => first = first.saturating_sub(1),
+                Event::KeyDown {keycode: Some(Keycode::Down), ..} => first += 1,
+                _ => {}
+            }
+        }
+
+        let (_, height) = canvas.output_size().expect("Couldn't get the window size");
+        let margin = height as i32 / 20;
+        let text_height = height / 28;
+        let pitch = text_height as i32 * 4 / 3;
+        let nb_visible = nb_visible_lines(height, margin * 3, pitch);
+        // also after a resize making more lines fit
+        first = first.min(lines.len().saturating_sub(nb_visible));
+        let font = &theme.font;
+        theme.draw_background(canvas);
+
+        let title_texture = create_texture_from_text(texture_creator, font, title, theme.highlight_color)
+            .expect("Couldn't render the results title");
+        canvas.copy(&title_texture, None, get_rect_from_text(font, title, margin, margin, height / 20 * 3 / 2))
+            .expect("Couldn't copy the results title");
+
+        for (row, line) in lines.iter().skip(first).take(nb_visible).enumerate() {
+            let texture = create_texture_from_text(texture_creator, font, line, theme.text_color)
+                .expect("Couldn't render a results line");
+            canvas.copy(&texture, None, get_rect_from_text(font, line, margin,
+                                                          margin * 3 + row as i32 * pitch,
+                                                          text_height))
+                .expect("Couldn't copy a results line");
+        }
+
+        canvas.present();
+        ::std::thread::sleep(Duration::new(0, 1_000_000_000u32 / 60));
+    }
+}
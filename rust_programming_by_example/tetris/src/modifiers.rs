@@ -0,0 +1,71 @@
+// Changes which can be applied on top of any mode. Big mode and the disabled
+// rotation change the game itself, the others only change how it is shown and
+// controlled.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Modifiers {
+    // 2x2 cases pieces, the board being half as wide and high
+    pub big: bool,
+    // board drawn mirrored, left and right swapped
+    pub mirror: bool,
+    // the locked blocks only show when lines are cleared
+    pub invisible: bool,
+    // every piece drawn with the same color
+    pub monochrome: bool,
+    pub no_rotation: bool,
+}
+
+pub const MODIFIER_NAMES: [&'static str; 5] = ["big", "mirror", "invisible", "monochrome", "no-rotation"];
+
+impl Modifiers {
+    pub fn none() -> Modifiers {
+        Modifiers::default()
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Modifiers::none()
+    }
+
+    fn flag_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "big" => Some(&mut self.big),
+            "mirror" => Some(&mut self.mirror),
+            "invisible" => Some(&mut self.invisible),
+            "monochrome" => Some(&mut self.monochrome),
+            "no-rotation" => Some(&mut self.no_rotation),
+            _ => None,
+        }
+    }
+
+    pub fn is_set(&self, name: &str) -> bool {
+        let mut modifiers = *self;
+        modifiers.flag_mut(name).is_some_and(|flag| *flag)
+    }
+
+    pub fn toggle(&mut self, name: &str) {
+        if let Some(flag) = self.flag_mut(name) {
+            *flag = !*flag;
+        }
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        MODIFIER_NAMES.iter().cloned().filter(|name| self.is_set(name)).collect()
+    }
+
+    // "big,mirror" for example, "none" without modifiers
+    pub fn to_text(&self) -> String {
+        if self.is_none() {
+            "none".to_owned()
+        } else {
+            self.names().join(",")
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Modifiers, String> {
+        let mut modifiers = Modifiers::none();
+        for name in text.split(',').map(|name| name.trim()).filter(|name| !name.is_empty() && *name != "none") {
+            let flag = modifiers.flag_mut(name).ok_or(format!("unknown modifier \"{}\"", name))?;
+            *flag = true;
+        }
+        Ok(modifiers)
+    }
+}
@@ -0,0 +1,176 @@
+use crate::board::Board;
+use crate::fumen;
+use crate::game_board::{GameConfig, Tetris, GARBAGE};
+use crate::tetrimino::{kind_from_char, PIECE_NAMES};
+
+use std::fs;
+
+// letter of the garbage cases in the text format
+const GARBAGE_CHAR: char = 'X';
+
+// A board position as it can be shared between players: the map and the
+// pieces, without the progress of the game it comes from.
+pub struct Position {
+    pub game_map: Board,
+    pub current: Option<u8>,
+    pub hold: Option<u8>,
+    pub queue: Vec<u8>,
+}
+
+// character of a map case in the text formats
+pub fn case_char(case: u8) -> char {
+    match case {
+        0 => '.',
+        GARBAGE => GARBAGE_CHAR,
+        case => PIECE_NAMES[case as usize - 1],
+    }
+}
+
+pub fn case_from_char(c: char) -> Result<u8, String> {
+    match c {
+        '.' => Ok(0),
+        c if c.to_ascii_uppercase() == GARBAGE_CHAR => Ok(GARBAGE),
+        c => kind_from_char(c).map(|kind| kind + 1).ok_or(format!("unknown case '{}'", c)),
+    }
+}
+
+fn pieces_text(pieces: &[u8]) -> String {
+    pieces.iter().map(|kind| PIECE_NAMES[*kind as usize]).collect()
+}
+
+fn parse_pieces(text: &str) -> Result<Vec<u8>, String> {
+    text.chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| kind_from_char(c).ok_or(format!("unknown piece '{}'", c)))
+        .collect()
+}
+
+fn parse_piece(text: &str) -> Result<Option<u8>, String> {
+    let pieces = parse_pieces(text)?;
+    if pieces.len() > 1 {
+        return Err(format!("only one piece expected: \"{}\"", text.trim()));
+    }
+    Ok(pieces.first().cloned())
+}
+
+impl Position {
+    pub fn from_game(tetris: &Tetris) -> Position {
+        Position {
+            game_map: tetris.visible_map(),
+            current: tetris.current_piece.as_ref().map(|piece| piece.kind()),
+            hold: tetris.hold,
+            queue: tetris.queue.iter().cloned().collect(),
+        }
+    }
+
+    // The map lines, aligned to the bottom of a `width` x `height` map. The
+    // lines which don't fit have to be empty.
+    pub fn board(&self, width: usize, height: usize) -> Result<Board, String> {
+        if self.game_map.width() != width {
+            return Err(format!("the position is {} cases wide instead of {}", self.game_map.width(), width));
+        }
+        let mut lines = vec![vec![0; width]; height.saturating_sub(self.game_map.len())];
+        let skipped = self.game_map.len().saturating_sub(height);
+        if self.game_map.iter().take(skipped).any(|line| line.iter().any(|case| *case != 0)) {
+            return Err(format!("the position is higher than {} lines", height));
+        }
+        lines.extend(self.game_map.iter().skip(skipped).cloned());
+        Board::from_lines(lines)
+    }
+
+    // Game starting from the position, the pieces given being played first
+    // before random ones.
+    pub fn create_game(&self, config: &GameConfig) -> Result<Tetris, String> {
+        let mut tetris = Tetris::from_config(config);
+        let (nb_columns, nb_lines) = config.board_size();
+        tetris.set_visible_map(&self.board(nb_columns, nb_lines)?);
+        let mut queue = self.current.iter().chain(self.queue.iter()).cloned().collect::<Vec<_>>();
+        queue.extend(tetris.queue.drain(..));
+        tetris.queue = queue.into_iter().collect();
+        tetris.hold = self.hold;
+        Ok(tetris)
+    }
+
+    // Positions look like this, all the map lines being given ('.' for an
+    // empty case, 'X' for garbage, a piece letter otherwise) and the pieces
+    // being optional:
+    //
+    // current: T
+    // hold: I
+    // queue: SZO
+    // board:
+    // ..........
+    // LLLLLLLLL.
+    pub fn to_text(&self) -> String {
+        let mut content = String::new();
+        if let Some(kind) = self.current {
+            content += &format!("current: {}\n", PIECE_NAMES[kind as usize]);
+        }
+        if let Some(kind) = self.hold {
+            content += &format!("hold: {}\n", PIECE_NAMES[kind as usize]);
+        }
+        content += &format!("queue: {}\nboard:\n", pieces_text(&self.queue));
+        for line in self.game_map.iter() {
+            content += &line.iter().map(|case| case_char(*case)).collect::<String>();
+            content += "\n";
+        }
+        content
+    }
+
+    pub fn parse(content: &str) -> Result<Position, String> {
+        let mut current = None;
+        let mut hold = None;
+        let mut queue = Vec::new();
+        let mut lines = Vec::new();
+        let mut in_board = false;
+
+        for line in content.lines() {
+            let line = line.trim();
+            if in_board {
+                if line.is_empty() {
+                    continue
+                }
+                lines.push(line.chars().map(case_from_char).collect::<Result<Vec<_>, _>>()?);
+            } else if let Some(piece) = line.strip_prefix("current:") {
+                current = parse_piece(piece)?;
+            } else if let Some(piece) = line.strip_prefix("hold:") {
+                hold = parse_piece(piece)?;
+            } else if let Some(pieces) = line.strip_prefix("queue:") {
+                queue = parse_pieces(pieces)?;
+            } else if line.starts_with("board:") {
+                in_board = true;
+            } else if !line.is_empty() {
+                return Err(format!("unexpected line \"{}\"", line));
+            }
+        }
+
+        Ok(Position {
+            game_map: Board::from_lines(lines)?,
+            current: current,
+            hold: hold,
+            queue: queue,
+        })
+    }
+
+    // either a fumen string (an URL being fine too) or the text format
+    pub fn import(text: &str) -> Result<Position, String> {
+        if fumen::is_fumen(text) {
+            fumen::decode(text)
+        } else {
+            Position::parse(text)
+        }
+    }
+
+    // `source` is a file, unless it is directly a fumen string
+    pub fn load(source: &str) -> Result<Position, String> {
+        if fumen::is_fumen(source) {
+            return fumen::decode(source);
+        }
+        let content = fs::read_to_string(source).map_err(|e| format!("{}: {}", source, e))?;
+        Position::import(&content).map_err(|e| format!("{}: {}", source, e))
+    }
+
+    pub fn to_fumen(&self) -> Result<String, String> {
+        fumen::encode(self)
+    }
+}
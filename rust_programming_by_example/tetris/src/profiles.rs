@@ -0,0 +1,366 @@
+use sdl2::keyboard::{KeyboardState, Keycode, Scancode};
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::modifiers::Modifiers;
+
+const PROFILES_HEADER: &'static str = "tetris-profiles";
+// the achievements are kept since version 2
+const PROFILES_VERSION: u32 = 2;
+const PROFILES_FILE: &'static str = "profiles.txt";
+// best games of the profiles, kept as replays
+const GHOSTS_DIR: &'static str = "ghosts";
+const DEFAULT_NAME: &'static str = "Player";
+const DEFAULT_DAS_FRAMES: u32 = 10;
+// limits of the settings, the auto shift ones being in frames
+pub const MIN_DAS_FRAMES: u32 = 4;
+pub const MAX_DAS_FRAMES: u32 = 20;
+pub const MAX_ARR_FRAMES: u32 = 5;
+
+// what the keys of a profile can be bound to
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Left,
+    Right,
+    Rotate,
+    SoftDrop,
+    HardDrop,
+    Hold,
+    // takes the last placement back in practice games
+    Rewind,
+}
+
+pub const ACTION_NAMES: [(Action, &'static str); 7] = [(Action::Left, "left"),
+                                                       (Action::Right, "right"),
+                                                       (Action::Rotate, "rotate"),
+                                                       (Action::SoftDrop, "soft-drop"),
+                                                       (Action::HardDrop, "hard-drop"),
+                                                       (Action::Hold, "hold"),
+                                                       (Action::Rewind, "rewind")];
+
+fn action_from_name(name: &str) -> Option<Action> {
+    ACTION_NAMES.iter().find(|(_, other)| *other == name).map(|(action, _)| *action)
+}
+
+// How a player controls the pieces.
+#[derive(Clone)]
+pub struct Controls {
+    pub keys: Vec<(Action, Keycode)>,
+    // frames a side key has to be held before the auto shift starts
+    pub das_frames: u32,
+    // frames between two moves of the auto shift, 0 sending the piece
+    // straight against the wall
+    pub arr_frames: u32,
+}
+
+impl Controls {
+    pub fn new() -> Controls {
+        Controls {
+            keys: vec![(Action::Left, Keycode::Left),
+                       (Action::Right, Keycode::Right),
+                       (Action::Rotate, Keycode::Up),
+                       (Action::SoftDrop, Keycode::Down),
+                       (Action::HardDrop, Keycode::Space),
+                       (Action::Hold, Keycode::C),
+                       (Action::Rewind, Keycode::Backspace)],
+            das_frames: DEFAULT_DAS_FRAMES,
+            arr_frames: 0,
+        }
+    }
+
+    pub fn key(&self, action: Action) -> Option<Keycode> {
+        self.keys.iter().find(|(other, _)| *other == action).map(|(_, keycode)| *keycode)
+    }
+
+    pub fn action(&self, keycode: Keycode) -> Option<Action> {
+        self.keys.iter().find(|(_, other)| *other == keycode).map(|(action, _)| *action)
+    }
+
+    // A key already used by another action gets the previous key of this
+    // one, so every action keeps a key.
+    pub fn bind(&mut self, action: Action, keycode: Keycode) {
+        let previous = self.key(action);
+        for (other, other_keycode) in self.keys.iter_mut() {
+            if *other == action {
+                *other_keycode = keycode;
+            } else if *other_keycode == keycode {
+                if let Some(previous) = previous {
+                    *other_keycode = previous;
+                }
+            }
+        }
+    }
+
+    pub fn is_held(&self, keyboard: &KeyboardState, action: Action) -> bool {
+        self.key(action)
+            .and_then(Scancode::from_keycode)
+            .is_some_and(|scancode| keyboard.is_scancode_pressed(scancode))
+    }
+}
+
+// What a player did over all the games played with a profile.
+#[derive(Clone, Default)]
+pub struct LifetimeStats {
+    pub nb_games: u32,
+    pub nb_lines: u32,
+    // best score of each mode played
+    pub best_scores: Vec<(String, u32)>,
+}
+
+impl LifetimeStats {
+    pub fn add_game(&mut self, mode: &str, score: u32, nb_lines: u32) {
+        self.nb_games += 1;
+        self.nb_lines += nb_lines;
+        match self.best_scores.iter_mut().find(|(other, _)| other == mode) {
+            Some((_, best)) => *best = score.max(*best),
+            None => self.best_scores.push((mode.to_owned(), score)),
+        }
+    }
+
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Games played: {}", self.nb_games),
+                             format!("Total lines: {}", self.nb_lines)];
+        lines.extend(self.best_scores.iter().map(|(mode, score)| format!("Best {}: {}", mode, score)));
+        lines
+    }
+}
+
+pub struct Profile {
+    pub name: String,
+    pub controls: Controls,
+    // name of the theme directory
+    pub theme: String,
+    pub lifetime: LifetimeStats,
+    // identifiers of the unlocked achievements, with when they were (in
+    // seconds since the Unix epoch)
+    pub achievements: Vec<(String, u64)>,
+}
+
+// the day of a timestamp, as YYYY-MM-DD in UTC
+pub fn format_date(timestamp: u64) -> String {
+    // from the days since the epoch to the civil date, counting in eras of
+    // 400 years starting on March 1st
+    let days = (timestamp / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+impl Profile {
+    pub fn new(name: &str, theme: &str) -> Profile {
+        Profile {
+            name: name.to_owned(),
+            controls: Controls::new(),
+            theme: theme.to_owned(),
+            lifetime: LifetimeStats::default(),
+            achievements: Vec::new(),
+        }
+    }
+
+    pub fn has_achievement(&self, id: &str) -> bool {
+        self.achievements.iter().any(|(other, _)| other == id)
+    }
+
+    // Returns false if the achievement was already unlocked.
+    pub fn unlock(&mut self, id: &str) -> bool {
+        if self.has_achievement(id) {
+            return false
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0);
+        self.achievements.push((id.to_owned(), now));
+        true
+    }
+
+    // replay of the best game of the profile in a mode, as for the
+    // highscores kept apart for every set of modifiers
+    pub fn ghost_path(&self, mode: &str, modifiers: &Modifiers) -> PathBuf {
+        let name = self.name
+                       .chars()
+                       .map(|c| if c.is_alphanumeric() { c } else { '_' })
+                       .collect::<String>();
+        config_dir().join(GHOSTS_DIR).join(format!("{}-{}-{}.txt", name, mode, modifiers.to_text()))
+    }
+}
+
+// The players known on this computer, one of them being selected.
+pub struct Profiles {
+    pub profiles: Vec<Profile>,
+    pub selected: usize,
+}
+
+// $XDG_CONFIG_HOME/tetris, or ~/.config/tetris, the current directory being
+// used when neither is known
+fn config_dir() -> PathBuf {
+    env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .map(|dir| dir.join("tetris"))
+        .unwrap_or_default()
+}
+
+pub fn profiles_path() -> PathBuf {
+    config_dir().join(PROFILES_FILE)
+}
+
+impl Profiles {
+    // a single profile, for the first launch
+    pub fn new(theme: &str) -> Profiles {
+        Profiles {
+            profiles: vec![Profile::new(DEFAULT_NAME, theme)],
+            selected: 0,
+        }
+    }
+
+    pub fn current(&self) -> &Profile {
+        &self.profiles[self.selected]
+    }
+
+    pub fn current_mut(&mut self) -> &mut Profile {
+        &mut self.profiles[self.selected]
+    }
+
+    // Adds a profile and selects it. Returns false if the name is already
+    // taken.
+    pub fn add(&mut self, name: &str, theme: &str) -> bool {
+        if self.profiles.iter().any(|profile| profile.name == name) {
+            return false
+        }
+        self.profiles.push(Profile::new(name, theme));
+        self.selected = self.profiles.len() - 1;
+        true
+    }
+
+    // The file has the selected profile name, then each profile with its
+    // settings and statistics:
+    //
+    // tetris-profiles 2
+    // selected Player
+    // profile Player
+    // key left Left
+    // key hard-drop Space
+    // das 10
+    // arr 0
+    // theme classic
+    // games 12
+    // lines 340
+    // best marathon 5230
+    // achievement first-tetris 1760000000
+    pub fn to_text(&self) -> String {
+        let mut content = format!("{} {}\n", PROFILES_HEADER, PROFILES_VERSION);
+        content += &format!("selected {}\n", self.current().name);
+        for profile in self.profiles.iter() {
+            content += &format!("profile {}\n", profile.name);
+            for (action, name) in ACTION_NAMES.iter() {
+                if let Some(keycode) = profile.controls.key(*action) {
+                    content += &format!("key {} {}\n", name, keycode.name());
+                }
+            }
+            content += &format!("das {}\n", profile.controls.das_frames);
+            content += &format!("arr {}\n", profile.controls.arr_frames);
+            content += &format!("theme {}\n", profile.theme);
+            content += &format!("games {}\n", profile.lifetime.nb_games);
+            content += &format!("lines {}\n", profile.lifetime.nb_lines);
+            for (mode, score) in profile.lifetime.best_scores.iter() {
+                content += &format!("best {} {}\n", mode, score);
+            }
+            for (id, timestamp) in profile.achievements.iter() {
+                content += &format!("achievement {} {}\n", id, timestamp);
+            }
+        }
+        content
+    }
+
+    pub fn parse(content: &str, theme: &str) -> Result<Profiles, String> {
+        let mut lines = content.lines();
+        match lines.next().map(|line| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(ref header) if header.len() == 2 && header[0] == PROFILES_HEADER => {
+                match header[1].parse::<u32>() {
+                    Ok(version) if version >= 1 && version <= PROFILES_VERSION => {},
+                    _ => return Err(format!("unsupported profiles version {}", header[1])),
+                }
+            },
+            _ => return Err("not a profiles file".to_owned()),
+        }
+
+        let mut profiles: Vec<Profile> = Vec::new();
+        let mut selected_name = String::new();
+        for line in lines {
+            if line.trim().is_empty() {
+                continue
+            }
+            let invalid = || format!("invalid line \"{}\"", line);
+            let mut parts = line.splitn(2, ' ');
+            let key = parts.next().unwrap_or("");
+            let value = parts.next().unwrap_or("").trim();
+            match key {
+                "selected" => selected_name = value.to_owned(),
+                "profile" if !value.is_empty() => profiles.push(Profile::new(value, theme)),
+                _ => {
+                    let profile = profiles.last_mut().ok_or_else(invalid)?;
+                    match key {
+                        "key" => {
+                            let mut parts = value.splitn(2, ' ');
+                            let action = parts.next().and_then(action_from_name).ok_or_else(invalid)?;
+                            let keycode = parts.next().and_then(Keycode::from_name).ok_or_else(invalid)?;
+                            profile.controls.bind(action, keycode);
+                        },
+                        "das" => profile.controls.das_frames = value.parse().map_err(|_| invalid())?,
+                        "arr" => profile.controls.arr_frames = value.parse().map_err(|_| invalid())?,
+                        "theme" => profile.theme = value.to_owned(),
+                        "games" => profile.lifetime.nb_games = value.parse().map_err(|_| invalid())?,
+                        "lines" => profile.lifetime.nb_lines = value.parse().map_err(|_| invalid())?,
+                        "best" => {
+                            let mut parts = value.splitn(2, ' ');
+                            let mode = parts.next().ok_or_else(invalid)?;
+                            let score = parts.next().and_then(|score| score.parse().ok()).ok_or_else(invalid)?;
+                            profile.lifetime.best_scores.push((mode.to_owned(), score));
+                        },
+                        "achievement" => {
+                            let mut parts = value.splitn(2, ' ');
+                            let id = parts.next().ok_or_else(invalid)?;
+                            let timestamp = parts.next().and_then(|time| time.parse().ok()).ok_or_else(invalid)?;
+                            profile.achievements.push((id.to_owned(), timestamp));
+                        },
+                        _ => return Err(invalid()),
+                    }
+                },
+            }
+        }
+        if profiles.is_empty() {
+            return Err("no profile".to_owned());
+        }
+        for profile in profiles.iter_mut() {
+            profile.controls.das_frames = profile.controls.das_frames.max(MIN_DAS_FRAMES).min(MAX_DAS_FRAMES);
+            profile.controls.arr_frames = profile.controls.arr_frames.min(MAX_ARR_FRAMES);
+        }
+        let selected = profiles.iter().position(|profile| profile.name == selected_name).unwrap_or(0);
+        Ok(Profiles { profiles: profiles, selected: selected })
+    }
+
+    // A missing file gives a single profile with the given theme.
+    pub fn load(theme: &str) -> Result<Profiles, String> {
+        let path = profiles_path();
+        match fs::read_to_string(&path) {
+            Ok(content) => Profiles::parse(&content, theme).map_err(|e| format!("{}: {}", path.display(), e)),
+            Err(_) => Ok(Profiles::new(theme)),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = profiles_path();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        fs::write(&path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+}
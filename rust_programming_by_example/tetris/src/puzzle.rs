@@ -0,0 +1,335 @@
+use crate::board::Board;
+use crate::events::GameListener;
+use crate::game_board::{ClearKind, GameEvent, Tetris, FRAMES_PER_SECOND};
+use crate::position::{case_char, case_from_char};
+use crate::tetrimino::{kind_from_char, PIECE_NAMES};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const PUZZLES_DIR: &'static str = "assets/puzzles";
+const SOLVED_PUZZLES_FILE: &'static str = "puzzles.txt";
+// pack where the puzzles made in the sandbox are saved
+const SANDBOX_PACK: &'static str = "sandbox";
+
+const BOARD_WIDTH: usize = 10;
+const BOARD_HEIGHT: usize = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Goal {
+    ClearLines(u32),
+    PerfectClear,
+    TSpinDouble,
+    // no garbage left on the board
+    ClearGarbage,
+    // as many points as possible before the time is up, in frames
+    TimeLimit(u32),
+}
+
+// M:SS
+fn format_time(frames: u32) -> String {
+    let secs = (frames + FRAMES_PER_SECOND - 1) / FRAMES_PER_SECOND;
+    format!("{}:{:02}", secs / 60, secs % 60)
+}
+
+impl Goal {
+    fn parse(text: &str) -> Option<Goal> {
+        let mut words = text.split_whitespace();
+        match words.next() {
+            Some("lines") => words.next()
+                                  .and_then(|nb| nb.parse::<u32>().ok())
+                                  .map(Goal::ClearLines),
+            Some("perfect-clear") => Some(Goal::PerfectClear),
+            Some("tspin-double") => Some(Goal::TSpinDouble),
+            Some("clear-garbage") => Some(Goal::ClearGarbage),
+            Some("time") => words.next()
+                                 .and_then(|secs| secs.parse::<u32>().ok())
+                                 .map(|secs| Goal::TimeLimit(secs * FRAMES_PER_SECOND)),
+            _ => None,
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match *self {
+            Goal::ClearLines(nb) => format!("lines {}", nb),
+            Goal::PerfectClear => "perfect-clear".to_owned(),
+            Goal::TSpinDouble => "tspin-double".to_owned(),
+            Goal::ClearGarbage => "clear-garbage".to_owned(),
+            Goal::TimeLimit(frames) => format!("time {}", frames / FRAMES_PER_SECOND),
+        }
+    }
+
+    // used to cycle through the goals in the sandbox
+    pub fn next(&self) -> Goal {
+        match *self {
+            Goal::ClearLines(nb) if nb < 4 => Goal::ClearLines(nb + 1),
+            Goal::ClearLines(_) => Goal::PerfectClear,
+            Goal::PerfectClear => Goal::TSpinDouble,
+            Goal::TSpinDouble | Goal::ClearGarbage | Goal::TimeLimit(_) => Goal::ClearLines(1),
+        }
+    }
+
+    pub fn description(&self) -> String {
+        match *self {
+            Goal::ClearLines(1) => "Clear 1 line".to_owned(),
+            Goal::ClearLines(nb) => format!("Clear {} lines", nb),
+            Goal::PerfectClear => "Perfect clear".to_owned(),
+            Goal::TSpinDouble => "T-spin double".to_owned(),
+            Goal::ClearGarbage => "Clear the garbage".to_owned(),
+            Goal::TimeLimit(frames) => format!("Score in {}", format_time(frames)),
+        }
+    }
+}
+
+// keeps track of how far the player is from the puzzle goal
+pub struct Objective {
+    pub goal: Goal,
+    lines_cleared: u32,
+    frames: u32,
+    completed: bool,
+}
+
+impl Objective {
+    pub fn new(goal: Goal) -> Objective {
+        Objective {
+            goal: goal,
+            lines_cleared: 0,
+            frames: 0,
+            completed: false,
+        }
+    }
+
+    // shown while playing, the time limit counting down
+    pub fn description(&self) -> String {
+        match self.goal {
+            Goal::TimeLimit(frames) => format!("Time left: {}", format_time(frames.saturating_sub(self.frames))),
+            goal => goal.description(),
+        }
+    }
+
+    // to be called once per frame, for the time limit
+    pub fn tick(&mut self) {
+        self.frames += 1;
+        self.completed |= matches!(self.goal, Goal::TimeLimit(frames) if self.frames >= frames);
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.completed
+    }
+}
+
+impl GameListener for Objective {
+    fn on_event(&mut self, _tetris: &Tetris, event: &GameEvent) {
+        self.completed |= match (self.goal, event) {
+            (Goal::ClearLines(nb), GameEvent::LinesCleared { count, .. }) => {
+                self.lines_cleared += count;
+                self.lines_cleared >= nb
+            },
+            (Goal::PerfectClear, GameEvent::PerfectClear) => true,
+            (Goal::TSpinDouble, GameEvent::LinesCleared { kind: ClearKind::TSpinDouble, .. }) => true,
+            (Goal::ClearGarbage, GameEvent::GarbageCleared) => true,
+            _ => false,
+        };
+    }
+}
+
+pub struct Puzzle {
+    pub name: String,
+    pub goal: Goal,
+    pub game_map: Board,
+    pub pieces: Vec<u8>,
+}
+
+impl Puzzle {
+    // Puzzle files look like this, board lines being aligned to the bottom
+    // of the map ('.' for an empty case, a piece letter otherwise):
+    //
+    // name: Tetris
+    // goal: lines 4
+    // pieces: I
+    // board:
+    // LLLLLLLLL.
+    pub fn parse(content: &str) -> Result<Puzzle, String> {
+        let mut name = None;
+        let mut goal = None;
+        let mut pieces = Vec::new();
+        let mut board = Vec::new();
+        let mut in_board = false;
+
+        for line in content.lines() {
+            let line = line.trim_end();
+            if in_board {
+                if line.is_empty() {
+                    continue
+                }
+                if line.chars().count() != BOARD_WIDTH {
+                    return Err(format!("board lines must be {} cases wide: \"{}\"", BOARD_WIDTH, line));
+                }
+                board.push(line.chars().map(case_from_char).collect::<Result<Vec<_>, _>>()?);
+            } else if line.starts_with("name:") {
+                name = Some(line["name:".len()..].trim().to_owned());
+            } else if line.starts_with("goal:") {
+                let text = &line["goal:".len()..];
+                goal = Some(Goal::parse(text).ok_or(format!("unknown goal \"{}\"", text.trim()))?);
+            } else if line.starts_with("pieces:") {
+                for c in line["pieces:".len()..].chars().filter(|c| !c.is_whitespace()) {
+                    pieces.push(kind_from_char(c).ok_or(format!("unknown piece '{}'", c))?);
+                }
+            } else if line.starts_with("board:") {
+                in_board = true;
+            } else if !line.is_empty() {
+                return Err(format!("unexpected line \"{}\"", line));
+            }
+        }
+
+        if board.len() > BOARD_HEIGHT {
+            return Err(format!("the board can't be higher than {} lines", BOARD_HEIGHT));
+        }
+        if pieces.is_empty() {
+            return Err("no pieces given".to_owned());
+        }
+        let mut game_map = vec![vec![0; BOARD_WIDTH]; BOARD_HEIGHT - board.len()];
+        game_map.extend(board);
+
+        Ok(Puzzle {
+            name: name.ok_or("missing name")?,
+            goal: goal.ok_or("missing goal")?,
+            game_map: Board::from_lines(game_map)?,
+            pieces: pieces,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Puzzle, String> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Puzzle::parse(&content).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn create_game(&self) -> Tetris {
+        let mut tetris = Tetris::new();
+        tetris.set_visible_map(&self.game_map);
+        tetris.queue = self.pieces.iter().cloned().collect();
+        tetris.random_pieces = false;
+        tetris
+    }
+
+    pub fn pieces_text(&self) -> String {
+        self.pieces.iter().map(|kind| PIECE_NAMES[*kind as usize]).collect()
+    }
+
+    // the opposite of `parse`, only the non-empty bottom lines of the board
+    // are written
+    pub fn to_text(&self) -> String {
+        let mut content = format!("name: {}\ngoal: {}\npieces: {}\nboard:\n",
+                                  self.name, self.goal.to_text(), self.pieces_text());
+        let first_line = self.game_map.iter()
+                                      .position(|line| line.iter().any(|case| *case != 0))
+                                      .unwrap_or(self.game_map.len());
+        for line in self.game_map.iter().skip(first_line) {
+            content += &line.iter().map(|case| case_char(*case)).collect::<String>();
+            content += "\n";
+        }
+        content
+    }
+
+    // Saves the puzzle after the others of the sandbox pack, numbering its
+    // name. Returns the path of the new file.
+    pub fn save_to_sandbox(&mut self) -> Result<PathBuf, String> {
+        let dir = Path::new(PUZZLES_DIR).join(SANDBOX_PACK);
+        fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        let nb_files = fs::read_dir(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?.count();
+        self.name = format!("{} {}", self.name, nb_files + 1);
+        let path = dir.join(format!("{:03}_{}.txt", nb_files + 1, SANDBOX_PACK));
+        fs::write(&path, self.to_text()).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+// a pack is a directory of puzzle files, played in file name order
+pub struct PuzzlePack {
+    pub name: String,
+    pub puzzles: Vec<Puzzle>,
+    solved: Vec<String>,
+}
+
+impl PuzzlePack {
+    pub fn load(dir: &Path) -> Result<PuzzlePack, String> {
+        let name = dir.file_name()
+                      .map(|name| name.to_string_lossy().into_owned())
+                      .ok_or(format!("{}: invalid pack directory", dir.display()))?;
+        let mut files = fs::read_dir(dir)
+            .map_err(|e| format!("{}: {}", dir.display(), e))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .collect::<Vec<PathBuf>>();
+        files.sort();
+
+        let mut puzzles = Vec::new();
+        for file in files {
+            puzzles.push(Puzzle::load(&file)?);
+        }
+        let prefix = format!("{}/", name);
+        let solved = load_solved_puzzles().into_iter()
+                                          .filter(|id| id.starts_with(&prefix))
+                                          .collect();
+        Ok(PuzzlePack {
+            name: name,
+            puzzles: puzzles,
+            solved: solved,
+        })
+    }
+
+    fn puzzle_id(&self, index: usize) -> String {
+        format!("{}/{}", self.name, self.puzzles[index].name)
+    }
+
+    pub fn is_solved(&self, index: usize) -> bool {
+        self.solved.contains(&self.puzzle_id(index))
+    }
+
+    pub fn nb_solved(&self) -> usize {
+        (0..self.puzzles.len()).filter(|index| self.is_solved(*index)).count()
+    }
+
+    pub fn mark_solved(&mut self, index: usize) {
+        if self.is_solved(index) {
+            return
+        }
+        let id = self.puzzle_id(index);
+        let mut all_solved = load_solved_puzzles();
+        all_solved.push(id.clone());
+        self.solved.push(id);
+        if fs::write(SOLVED_PUZZLES_FILE, all_solved.join("\n") + "\n").is_err() {
+            println!("Couldn't save the solved puzzles");
+        }
+    }
+}
+
+pub fn load_packs() -> Vec<PuzzlePack> {
+    let mut dirs = match fs::read_dir(PUZZLES_DIR) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                              .filter(|path| path.is_dir())
+                              .collect::<Vec<PathBuf>>(),
+        Err(_) => Vec::new(),
+    };
+    dirs.sort();
+    dirs.iter()
+        .filter_map(|dir| match PuzzlePack::load(dir) {
+            Ok(pack) => Some(pack),
+            Err(e) => {
+                println!("Couldn't load puzzle pack: {}", e);
+                None
+            },
+        })
+        .collect()
+}
+
+fn load_solved_puzzles() -> Vec<String> {
+    match fs::read_to_string(SOLVED_PUZZLES_FILE) {
+        Ok(content) => content.lines()
+                              .filter(|line| !line.is_empty())
+                              .map(|line| line.to_owned())
+                              .collect(),
+        Err(_) => Vec::new(),
+    }
+}
@@ -0,0 +1,45 @@
+extern crate rand;
+
+// Piece generator with a state small enough to be saved: a xorshift64*
+// generator, rerolling once when it gives the same piece twice in a row.
+#[derive(Clone)]
+pub struct Randomizer {
+    pub state: u64,
+    // last generated kind, 7 when none
+    pub previous: u8,
+}
+
+impl Randomizer {
+    pub fn new(seed: u64) -> Randomizer {
+        Randomizer {
+            // xorshift gets stuck on 0
+            state: if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed },
+            previous: 7,
+        }
+    }
+
+    pub fn from_entropy() -> Randomizer {
+        Randomizer::new(rand::random::<u64>())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    // a number from 0 to `max` excluded
+    pub fn next_below(&mut self, max: usize) -> usize {
+        (self.next_u64() % max as u64) as usize
+    }
+
+    pub fn next_kind(&mut self) -> u8 {
+        let mut kind = (self.next_u64() % 7) as u8;
+        if kind == self.previous {
+            kind = (self.next_u64() % 7) as u8;
+        }
+        self.previous = kind;
+        kind
+    }
+}
@@ -0,0 +1,346 @@
+use crate::events::{simulate_frame, EventBus};
+use crate::game_board::{GameConfig, GameEvent, GameOver, Input, Tetris, FRAMES_PER_SECOND};
+use crate::modifiers::Modifiers;
+
+use std::fs;
+
+const REPLAY_HEADER: &'static str = "tetris-replay";
+const REPLAY_VERSION: u32 = 1;
+// longest replay accepted, so that checking one always ends in a reasonable
+// time
+const MAX_FRAMES: u32 = 4 * 3600 * FRAMES_PER_SECOND;
+// lines without which a replay can't be played back as it was recorded
+const REQUIRED_LINES: [&'static str; 4] = ["seed", "board", "level", "frames"];
+
+const INPUT_NAMES: [(Input, &'static str); 10] = [(Input::Left, "left"),
+                                                  (Input::Right, "right"),
+                                                  (Input::DasLeft, "das-left"),
+                                                  (Input::DasRight, "das-right"),
+                                                  (Input::RepeatLeft, "repeat-left"),
+                                                  (Input::RepeatRight, "repeat-right"),
+                                                  (Input::Rotate, "rotate"),
+                                                  (Input::SoftDrop, "soft-drop"),
+                                                  (Input::HardDrop, "hard-drop"),
+                                                  (Input::Hold, "hold")];
+
+fn input_name(input: Input) -> &'static str {
+    INPUT_NAMES.iter().find(|(other, _)| *other == input).map(|(_, name)| *name).unwrap_or("")
+}
+
+fn input_from_name(name: &str) -> Option<Input> {
+    INPUT_NAMES.iter().find(|(_, other)| *other == name).map(|(input, _)| *input)
+}
+
+// State of the game right after a piece has been locked. They aren't needed
+// to play a replay back, but tell where a simulation first went differently.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Checkpoint {
+    // frame during which the piece has been locked
+    pub frame: u32,
+    pub score: u32,
+    pub nb_lines: u32,
+    pub board: u64,
+}
+
+impl Checkpoint {
+    // the checkpoint of the frame that has just been simulated, if a piece
+    // has been locked during it
+    fn after_frame(frame: u32, tetris: &Tetris) -> Option<Checkpoint> {
+        if !tetris.events.iter().any(|event| matches!(event, GameEvent::PieceLocked { .. })) {
+            return None;
+        }
+        Some(Checkpoint {
+            frame: frame,
+            score: tetris.score,
+            nb_lines: tetris.nb_lines,
+            board: tetris.game_map.fingerprint(),
+        })
+    }
+}
+
+// First checkpoint on which the replay and the simulation disagree, one of
+// them being missing if a piece has been locked on one side only.
+pub struct Divergence {
+    pub frame: u32,
+    pub recorded: Option<Checkpoint>,
+    pub simulated: Option<Checkpoint>,
+}
+
+// What really happens when a replay is played back.
+pub struct Verification {
+    pub score: u32,
+    pub nb_lines: u32,
+    pub level: u32,
+    pub nb_frames: u32,
+    // number of frames played when each line was cleared, in order
+    pub line_frames: Vec<u32>,
+    pub game_over: Option<GameOver>,
+    // only looked for when the replay has checkpoints
+    pub divergence: Option<Divergence>,
+}
+
+impl Verification {
+    // only the claimed values are compared, along with the checkpoints
+    pub fn matches(&self, score: Option<u32>, nb_lines: Option<u32>) -> bool {
+        self.divergence.is_none() &&
+            score.is_none_or(|score| score == self.score) &&
+            nb_lines.is_none_or(|nb_lines| nb_lines == self.nb_lines)
+    }
+}
+
+// A game is entirely defined by its configuration and the inputs given at
+// each simulated frame, which is all a replay needs to keep.
+pub struct Replay {
+    pub config: GameConfig,
+    // frame at which each input was applied, in order
+    pub inputs: Vec<(u32, Input)>,
+    pub nb_frames: u32,
+    pub checkpoints: Vec<Checkpoint>,
+}
+
+impl Replay {
+    pub fn new(config: &GameConfig) -> Replay {
+        Replay {
+            config: config.clone(),
+            inputs: Vec::new(),
+            nb_frames: 0,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    pub fn create_game(&self) -> Tetris {
+        Tetris::from_config(&self.config)
+    }
+
+    // to be called once per simulated frame, with the inputs applied during it
+    // and the game as it is after it (before its events are taken)
+    pub fn record_frame(&mut self, inputs: &[Input], tetris: &Tetris) {
+        for input in inputs {
+            self.inputs.push((self.nb_frames, *input));
+        }
+        if let Some(checkpoint) = Checkpoint::after_frame(self.nb_frames, tetris) {
+            self.checkpoints.push(checkpoint);
+        }
+        self.nb_frames += 1;
+    }
+
+    // Plays the replay back with the engine rules, whatever the recorded
+    // checkpoints say, comparing them with the simulated ones on the way.
+    pub fn verify(&self) -> Verification {
+        let mut tetris = self.create_game();
+        let mut playback = Playback::new(self);
+        // the simulation is recorded again to get its checkpoints
+        let mut simulation = Replay::new(&self.config);
+        let mut line_frames = Vec::new();
+        let bus = EventBus::new();
+        while !playback.is_finished() && !tetris.is_game_over() {
+            simulate_frame(&mut tetris, &playback.next_frame(), None, Some(&mut simulation), &bus);
+            line_frames.resize(tetris.nb_lines as usize, simulation.nb_frames);
+        }
+        let simulated = simulation.checkpoints;
+
+        let divergence = if self.checkpoints.is_empty() {
+            None
+        } else {
+            let nb_checkpoints = self.checkpoints.len().max(simulated.len());
+            (0..nb_checkpoints)
+                .map(|index| (self.checkpoints.get(index).cloned(), simulated.get(index).cloned()))
+                .find(|(recorded, simulated)| recorded != simulated)
+                .map(|(recorded, simulated)| {
+                    let frame = recorded.iter().chain(simulated.iter()).map(|checkpoint| checkpoint.frame).min();
+                    Divergence {
+                        frame: frame.unwrap_or(0),
+                        recorded: recorded,
+                        simulated: simulated,
+                    }
+                })
+        };
+        Verification {
+            score: tetris.score,
+            nb_lines: tetris.nb_lines,
+            level: tetris.current_level,
+            nb_frames: simulation.nb_frames,
+            line_frames: line_frames,
+            game_over: tetris.game_over,
+            divergence: divergence,
+        }
+    }
+
+    // The replay file looks like this, the inputs lines being the frame
+    // number followed by the input name, and the checkpoints lines the frame
+    // number followed by the score, the number of lines and the board
+    // fingerprint:
+    //
+    // tetris-replay 1
+    // board 10 16
+    // level 1
+    // seed 8595196474915426013
+    // entry-delay 0
+    // initial-actions 1
+    // modifiers none
+    // garbage 0 0
+    // trainer 0
+    // frames 1200
+    // inputs
+    // 12 left
+    // 30 hard-drop
+    // 30 check 1 0 a1b2c3d4e5f60718
+    pub fn serialize(&self) -> String {
+        let mut content = format!("{} {}\n", REPLAY_HEADER, REPLAY_VERSION);
+        content += &format!("board {} {}\n", self.config.nb_columns, self.config.nb_lines);
+        content += &format!("level {}\n", self.config.level);
+        content += &format!("seed {}\n", self.config.seed);
+        content += &format!("entry-delay {}\n", self.config.entry_delay);
+        content += &format!("initial-actions {}\n", self.config.initial_actions as u8);
+        content += &format!("modifiers {}\n", self.config.modifiers.to_text());
+        content += &format!("garbage {} {}\n", self.config.garbage_lines, self.config.garbage_interval);
+        content += &format!("trainer {}\n", self.config.trainer as u8);
+        content += &format!("frames {}\n", self.nb_frames);
+        content += "inputs\n";
+        let mut checkpoints = self.checkpoints.iter().peekable();
+        for (frame, input) in self.inputs.iter() {
+            while let Some(checkpoint) = checkpoints.next_if(|checkpoint| checkpoint.frame < *frame) {
+                content += &checkpoint_line(checkpoint);
+            }
+            content += &format!("{} {}\n", frame, input_name(*input));
+        }
+        for checkpoint in checkpoints {
+            content += &checkpoint_line(checkpoint);
+        }
+        content
+    }
+
+    pub fn parse(content: &str) -> Result<Replay, String> {
+        let mut lines = content.lines();
+        match lines.next().map(|line| line.split_whitespace().collect::<Vec<_>>()) {
+            Some(ref header) if header.len() == 2 && header[0] == REPLAY_HEADER => {
+                if header[1].parse::<u32>() != Ok(REPLAY_VERSION) {
+                    return Err(format!("unsupported replay version {}", header[1]));
+                }
+            },
+            _ => return Err("not a replay file".to_owned()),
+        }
+
+        let mut replay = Replay::new(&GameConfig::new());
+        let mut in_inputs = false;
+        let mut seen = Vec::new();
+        for line in lines {
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            if words.is_empty() {
+                continue
+            }
+            let invalid = || format!("invalid line \"{}\"", line);
+            if in_inputs && words.len() == 5 && words[1] == "check" {
+                let checkpoint = Checkpoint {
+                    frame: words[0].parse().map_err(|_| invalid())?,
+                    score: words[2].parse().map_err(|_| invalid())?,
+                    nb_lines: words[3].parse().map_err(|_| invalid())?,
+                    board: u64::from_str_radix(words[4], 16).map_err(|_| invalid())?,
+                };
+                if checkpoint.frame >= replay.nb_frames ||
+                   replay.checkpoints.last().is_some_and(|last| last.frame >= checkpoint.frame) {
+                    return Err(format!("checkpoint out of order \"{}\"", line));
+                }
+                replay.checkpoints.push(checkpoint);
+                continue
+            }
+            if in_inputs {
+                if words.len() != 2 {
+                    return Err(invalid());
+                }
+                let frame = words[0].parse::<u32>().map_err(|_| invalid())?;
+                let input = input_from_name(words[1]).ok_or_else(invalid)?;
+                if frame >= replay.nb_frames || replay.inputs.last().is_some_and(|(last, _)| *last > frame) {
+                    return Err(format!("input out of order \"{}\"", line));
+                }
+                replay.inputs.push((frame, input));
+                continue
+            }
+            match (words[0], words.len()) {
+                ("board", 3) => {
+                    replay.config.nb_columns = words[1].parse().map_err(|_| invalid())?;
+                    replay.config.nb_lines = words[2].parse().map_err(|_| invalid())?;
+                },
+                ("level", 2) => replay.config.level = words[1].parse().map_err(|_| invalid())?,
+                ("seed", 2) => replay.config.seed = words[1].parse().map_err(|_| invalid())?,
+                ("entry-delay", 2) => replay.config.entry_delay = words[1].parse().map_err(|_| invalid())?,
+                ("initial-actions", 2) => replay.config.initial_actions = match words[1] {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(invalid()),
+                },
+                ("garbage", 3) => {
+                    replay.config.garbage_lines = words[1].parse().map_err(|_| invalid())?;
+                    replay.config.garbage_interval = words[2].parse().map_err(|_| invalid())?;
+                },
+                ("trainer", 2) => replay.config.trainer = match words[1] {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(invalid()),
+                },
+                ("modifiers", 2) => replay.config.modifiers = Modifiers::parse(words[1])?,
+                ("frames", 2) => {
+                    replay.nb_frames = words[1].parse().map_err(|_| invalid())?;
+                    if replay.nb_frames > MAX_FRAMES {
+                        return Err(format!("replays can't be longer than {} frames", MAX_FRAMES));
+                    }
+                },
+                ("inputs", 1) => in_inputs = true,
+                _ => return Err(invalid()),
+            }
+            seen.push(words[0]);
+        }
+        if let Some(name) = REQUIRED_LINES.iter().find(|name| !seen.contains(name)) {
+            return Err(format!("missing {}", name));
+        }
+        replay.config.check()?;
+        Ok(replay)
+    }
+
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let content = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        Replay::parse(&content).map_err(|e| format!("{}: {}", path, e))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        fs::write(path, self.serialize()).map_err(|e| format!("{}: {}", path, e))
+    }
+}
+
+fn checkpoint_line(checkpoint: &Checkpoint) -> String {
+    format!("{} check {} {} {:016x}\n", checkpoint.frame, checkpoint.score, checkpoint.nb_lines, checkpoint.board)
+}
+
+// Gives back the inputs of a replay, frame after frame.
+pub struct Playback<'a> {
+    replay: &'a Replay,
+    frame: u32,
+    next_input: usize,
+}
+
+impl<'a> Playback<'a> {
+    pub fn new(replay: &'a Replay) -> Playback<'a> {
+        Playback {
+            replay: replay,
+            frame: 0,
+            next_input: 0,
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Vec<Input> {
+        let mut inputs = Vec::new();
+        while let Some((frame, input)) = self.replay.inputs.get(self.next_input) {
+            if *frame != self.frame {
+                break
+            }
+            inputs.push(*input);
+            self.next_input += 1;
+        }
+        self.frame += 1;
+        inputs
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.frame >= self.replay.nb_frames
+    }
+}
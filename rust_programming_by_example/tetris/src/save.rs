@@ -0,0 +1,192 @@
+use crate::board::Board;
+use crate::game_board::Tetris;
+use crate::modifiers::Modifiers;
+use crate::randomizer::Randomizer;
+use crate::stats::Stats;
+use crate::tetrimino::create_tetrimino;
+
+use std::fs;
+use std::path::Path;
+
+const SAVE_FILE: &'static str = "savegame.txt";
+const SAVE_HEADER: &'static str = "tetris-save";
+const SAVE_VERSION: u32 = 1;
+
+// The save file is made of "key values" lines followed by the map, one line
+// of digits per map line (the vanish zone included):
+//
+// tetris-save 1
+// level 1
+// score 12
+// lines 0
+// random_pieces 1
+// randomizer 8595196474915426013 3
+// queue 0 5 2
+// piece 6 4 3 1
+// hold 2 1
+// drop_frames 20
+// entry_delay 0
+// initial_actions 1
+// modifiers big,mirror
+// garbage 600 120 7253301843126374310
+// stats 3120 25 101 4 0 2 3 0 0 1 4 3 4 3 4 4 3 2 1 0 1 0 1 0
+// map
+// 0000000000
+// ...
+pub fn serialize(tetris: &Tetris, stats: &Stats) -> String {
+    let mut content = format!("{} {}\n", SAVE_HEADER, SAVE_VERSION);
+    content += &format!("level {}\n", tetris.current_level);
+    content += &format!("score {}\n", tetris.score);
+    content += &format!("lines {}\n", tetris.nb_lines);
+    content += &format!("random_pieces {}\n", tetris.random_pieces as u8);
+    content += &format!("randomizer {} {}\n", tetris.randomizer.state, tetris.randomizer.previous);
+    content += "queue";
+    for kind in tetris.queue.iter() {
+        content += &format!(" {}", kind);
+    }
+    content += "\n";
+    if let Some(ref piece) = tetris.current_piece {
+        content += &format!("piece {} {} {} {}\n", piece.kind(), piece.x, piece.y, piece.current_state);
+    }
+    if let Some(kind) = tetris.hold {
+        content += &format!("hold {} {}\n", kind, tetris.can_hold as u8);
+    }
+    content += &format!("drop_frames {}\n", tetris.drop_frames);
+    content += &format!("entry_delay {}\n", tetris.entry_delay);
+    content += &format!("initial_actions {}\n", tetris.initial_actions as u8);
+    content += &format!("modifiers {}\n", tetris.modifiers.to_text());
+    content += &format!("garbage {} {} {}\n", tetris.garbage_interval, tetris.garbage_frames,
+                        tetris.garbage_randomizer.state);
+    content += "stats";
+    for value in stats.to_values() {
+        content += &format!(" {}", value);
+    }
+    content += "\n";
+    content += "map\n";
+    for line in tetris.game_map.iter() {
+        content += &line.iter().map(|case| case.to_string()).collect::<String>();
+        content += "\n";
+    }
+    content
+}
+
+fn parse_values<T: std::str::FromStr>(values: &[&str]) -> Result<Vec<T>, String> {
+    values.iter()
+          .map(|value| value.parse::<T>().map_err(|_| format!("invalid value \"{}\"", value)))
+          .collect()
+}
+
+fn parse_value<T: std::str::FromStr>(key: &str, values: &[&str]) -> Result<T, String> {
+    let mut values = parse_values::<T>(values)?;
+    if values.len() != 1 {
+        return Err(format!("\"{}\" expects one value", key));
+    }
+    Ok(values.remove(0))
+}
+
+pub fn deserialize(content: &str) -> Result<(Tetris, Stats), String> {
+    let mut lines = content.lines();
+    match lines.next().map(|line| line.split_whitespace().collect::<Vec<_>>()) {
+        Some(ref header) if header.len() == 2 && header[0] == SAVE_HEADER => {
+            if header[1].parse::<u32>() != Ok(SAVE_VERSION) {
+                return Err(format!("unsupported save version {}", header[1]));
+            }
+        },
+        _ => return Err("not a save file".to_owned()),
+    }
+
+    let mut tetris = Tetris::new();
+    let mut in_map = false;
+    let mut game_map = Vec::new();
+    let mut stats = None;
+    for line in lines {
+        if in_map {
+            let map_line = line.chars()
+                               .map(|c| c.to_digit(10).map(|case| case as u8).ok_or(format!("invalid case '{}'", c)))
+                               .collect::<Result<Vec<u8>, String>>()?;
+            game_map.push(map_line);
+            continue
+        }
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if words.is_empty() {
+            continue
+        }
+        let (key, values) = (words[0], &words[1..]);
+        match key {
+            "level" => tetris.current_level = parse_value(key, values)?,
+            "score" => tetris.score = parse_value(key, values)?,
+            "lines" => tetris.nb_lines = parse_value(key, values)?,
+            "random_pieces" => tetris.random_pieces = parse_value::<u8>(key, values)? != 0,
+            "randomizer" => {
+                if values.len() != 2 {
+                    return Err("\"randomizer\" expects two values".to_owned());
+                }
+                tetris.randomizer = Randomizer {
+                    state: parse_value(key, &values[..1])?,
+                    previous: parse_value(key, &values[1..])?,
+                };
+            },
+            "queue" => {
+                tetris.queue = parse_values::<u8>(values)?.into_iter().collect();
+            },
+            "piece" => {
+                if values.len() != 4 {
+                    return Err("\"piece\" expects four values".to_owned());
+                }
+                let kind = parse_value::<u8>(key, &values[..1])?;
+                if kind >= 7 {
+                    return Err(format!("invalid piece kind {}", kind));
+                }
+                let mut piece = create_tetrimino(kind);
+                piece.x = parse_value(key, &values[1..2])?;
+                piece.y = parse_value(key, &values[2..3])?;
+                piece.current_state = parse_value(key, &values[3..])?;
+                tetris.current_piece = Some(piece);
+            },
+            "hold" => {
+                if values.len() != 2 {
+                    return Err("\"hold\" expects two values".to_owned());
+                }
+                tetris.hold = Some(parse_value(key, &values[..1])?);
+                tetris.can_hold = parse_value::<u8>(key, &values[1..])? != 0;
+            },
+            "drop_frames" => tetris.drop_frames = parse_value(key, values)?,
+            "entry_delay" => tetris.entry_delay = parse_value(key, values)?,
+            "initial_actions" => tetris.initial_actions = parse_value::<u8>(key, values)? != 0,
+            "garbage" => {
+                if values.len() != 3 {
+                    return Err("\"garbage\" expects three values".to_owned());
+                }
+                tetris.garbage_interval = parse_value(key, &values[..1])?;
+                tetris.garbage_frames = parse_value(key, &values[1..2])?;
+                tetris.garbage_randomizer = Randomizer::new(parse_value(key, &values[2..])?);
+            },
+            "stats" => stats = Some(parse_values::<u32>(values)?),
+            "modifiers" => tetris.modifiers = Modifiers::parse(&values.join(","))?,
+            "map" => in_map = true,
+            _ => return Err(format!("unknown entry \"{}\"", key)),
+        }
+    }
+
+    tetris.game_map = Board::from_lines(game_map)?;
+    tetris.check()?;
+    let stats = Stats::from_values(&stats.ok_or("missing stats")?, tetris.game_map.width())?;
+    Ok((tetris, stats))
+}
+
+pub fn has_saved_game() -> bool {
+    Path::new(SAVE_FILE).is_file()
+}
+
+pub fn save_game(tetris: &Tetris, stats: &Stats) -> bool {
+    fs::write(SAVE_FILE, serialize(tetris, stats)).is_ok()
+}
+
+// the save is removed once loaded: a game can only be resumed once, but an
+// invalid save is left for the player to look at
+pub fn load_game() -> Result<(Tetris, Stats), String> {
+    let content = fs::read_to_string(SAVE_FILE).map_err(|e| format!("{}: {}", SAVE_FILE, e))?;
+    let game = deserialize(&content).map_err(|e| format!("{}: {}", SAVE_FILE, e))?;
+    let _ = fs::remove_file(SAVE_FILE);
+    Ok(game)
+}
@@ -0,0 +1,62 @@
+use std::fs;
+
+use crate::theme::{DisplayOptions, Palette};
+
+const SETTINGS_FILE: &'static str = "settings.txt";
+
+pub fn on_off(value: bool) -> &'static str {
+    if value { "on" } else { "off" }
+}
+
+// options chosen from the menu, kept between launches
+pub struct Settings {
+    // name of the theme directory
+    pub theme: String,
+    pub display: DisplayOptions,
+    // a single attempt at each daily challenge
+    pub daily_one_attempt: bool,
+    // the sprint and ultra games race the personal best
+    pub ghost: bool,
+}
+
+impl Settings {
+    pub fn load() -> Settings {
+        let mut settings = Settings {
+            theme: "classic".to_owned(),
+            display: DisplayOptions {
+                palette: Palette::Theme,
+                patterns: false,
+                high_contrast: false,
+            },
+            daily_one_attempt: false,
+            ghost: true,
+        };
+        if let Ok(content) = fs::read_to_string(SETTINGS_FILE) {
+            for line in content.lines() {
+                let mut parts = line.splitn(2, ':');
+                let key = parts.next().unwrap_or("").trim();
+                let value = parts.next().unwrap_or("").trim();
+                match key {
+                    "theme" => settings.theme = value.to_owned(),
+                    "palette" => settings.display.palette = Palette::from_name(value).unwrap_or(Palette::Theme),
+                    "patterns" => settings.display.patterns = value == "on",
+                    "high-contrast" => settings.display.high_contrast = value == "on",
+                    "daily-one-attempt" => settings.daily_one_attempt = value == "on",
+                    "ghost" => settings.ghost = value == "on",
+                    _ => {}
+                }
+            }
+        }
+        settings
+    }
+
+    pub fn save(&self) -> bool {
+        fs::write(SETTINGS_FILE, format!("theme: {}\npalette: {}\npatterns: {}\nhigh-contrast: {}\ndaily-one-attempt: {}\nghost: {}\n",
+                                         self.theme,
+                                         self.display.palette.name(),
+                                         on_off(self.display.patterns),
+                                         on_off(self.display.high_contrast),
+                                         on_off(self.daily_one_attempt),
+                                         on_off(self.ghost))).is_ok()
+    }
+}
@@ -0,0 +1,210 @@
+use crate::finesse::min_inputs;
+use crate::events::GameListener;
+use crate::game_board::{GameEvent, Input, Tetris, FRAMES_PER_SECOND};
+use crate::tetrimino::PIECE_NAMES;
+
+// lines sent for each kind of clear, singles to tetrises then T-spin singles
+// to triples
+const ATTACK: [u32; 7] = [0, 1, 2, 4, 2, 4, 6];
+const PERFECT_CLEAR_ATTACK: u32 = 10;
+// extra lines sent depending on the number of consecutive clears
+const COMBO_ATTACK: [u32; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+
+// counters given by `to_values`
+const NB_VALUES: usize = 24;
+
+const CLEAR_NAMES: [&'static str; 7] = ["Singles", "Doubles", "Triples", "Tetrises",
+                                        "T-spin singles", "T-spin doubles", "T-spin triples"];
+
+// What the player did during a game, fed by the engine events and the key
+// presses.
+pub struct Stats {
+    // game duration, in simulation frames
+    pub frames: u32,
+    pub nb_pieces: u32,
+    pub pieces: [u32; 7],
+    pub keys: u32,
+    pub attack: u32,
+    // singles to tetrises, then T-spin singles to triples
+    pub clears: [u32; 7],
+    pub perfect_clears: u32,
+    pub max_combo: u32,
+    pub finesse_faults: u32,
+    consecutive_clears: u32,
+    last_piece_cleared: bool,
+    // horizontal moves and rotations done with the current piece
+    piece_inputs: u32,
+    nb_columns: usize,
+}
+
+impl Stats {
+    pub fn new(nb_columns: usize) -> Stats {
+        Stats {
+            frames: 0,
+            nb_pieces: 0,
+            pieces: [0; 7],
+            keys: 0,
+            attack: 0,
+            clears: [0; 7],
+            perfect_clears: 0,
+            max_combo: 0,
+            finesse_faults: 0,
+            consecutive_clears: 0,
+            last_piece_cleared: false,
+            piece_inputs: 0,
+            nb_columns: nb_columns,
+        }
+    }
+
+    // Called before `input` is applied to `tetris`. Only the inputs moving or
+    // rotating the piece count for the finesse, the piece coming out of the
+    // hold starting from zero.
+    pub fn record_input(&mut self, input: Input, tetris: &Tetris) {
+        match input {
+            Input::Left | Input::Right | Input::Rotate => self.piece_inputs += 1,
+            // the auto shift comes from a key press already counted as a tap
+            Input::DasLeft | Input::DasRight | Input::RepeatLeft | Input::RepeatRight => return,
+            Input::Hold if tetris.can_hold => self.piece_inputs = 0,
+            Input::SoftDrop | Input::HardDrop | Input::Hold => {},
+        }
+        self.keys += 1;
+    }
+
+    // called for every simulated frame
+    pub fn tick(&mut self) {
+        self.frames += 1;
+    }
+
+    pub fn elapsed_secs(&self) -> f64 {
+        self.frames as f64 / FRAMES_PER_SECOND as f64
+    }
+
+    pub fn pieces_per_second(&self) -> f64 {
+        let elapsed = self.elapsed_secs();
+        if elapsed > 0. { self.nb_pieces as f64 / elapsed } else { 0. }
+    }
+
+    pub fn attack_per_minute(&self) -> f64 {
+        let elapsed = self.elapsed_secs();
+        if elapsed > 0. { self.attack as f64 * 60. / elapsed } else { 0. }
+    }
+
+    pub fn keys_per_piece(&self) -> f64 {
+        if self.nb_pieces > 0 { self.keys as f64 / self.nb_pieces as f64 } else { 0. }
+    }
+
+    // short lines shown next to the grid while playing
+    pub fn live_lines(&self) -> Vec<String> {
+        vec![format!("PPS: {:.2}", self.pieces_per_second()),
+             format!("APM: {:.1}", self.attack_per_minute()),
+             format!("KPP: {:.2}", self.keys_per_piece()),
+             format!("Faults: {}", self.finesse_faults)]
+    }
+
+    // everything, for the results screen
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Time: {:.1} s", self.elapsed_secs()),
+                             format!("Pieces: {} ({:.2}/s)", self.nb_pieces, self.pieces_per_second()),
+                             format!("Attack: {} ({:.1}/min)", self.attack, self.attack_per_minute()),
+                             format!("Keys per piece: {:.2}", self.keys_per_piece()),
+                             format!("Finesse faults: {}", self.finesse_faults),
+                             format!("Max combo: {}", self.max_combo),
+                             format!("Perfect clears: {}", self.perfect_clears)];
+        lines.push(PIECE_NAMES.iter()
+                              .zip(self.pieces.iter())
+                              .map(|(name, nb)| format!("{}:{}", name, nb))
+                              .collect::<Vec<_>>()
+                              .join(" "));
+        for (name, nb) in CLEAR_NAMES.iter().zip(self.clears.iter()) {
+            if *nb > 0 {
+                lines.push(format!("{}: {}", name, nb));
+            }
+        }
+        lines
+    }
+
+    // every counter, so a saved game can be resumed with its statistics
+    pub fn to_values(&self) -> Vec<u32> {
+        let mut values = vec![self.frames, self.nb_pieces, self.keys, self.attack, self.perfect_clears,
+                              self.max_combo, self.finesse_faults, self.consecutive_clears,
+                              self.last_piece_cleared as u32, self.piece_inputs];
+        values.extend(self.pieces.iter());
+        values.extend(self.clears.iter());
+        values
+    }
+
+    pub fn from_values(values: &[u32], nb_columns: usize) -> Result<Stats, String> {
+        if values.len() != NB_VALUES {
+            return Err(format!("the statistics need {} values", NB_VALUES));
+        }
+        let mut stats = Stats::new(nb_columns);
+        stats.frames = values[0];
+        stats.nb_pieces = values[1];
+        stats.keys = values[2];
+        stats.attack = values[3];
+        stats.perfect_clears = values[4];
+        stats.max_combo = values[5];
+        stats.finesse_faults = values[6];
+        stats.consecutive_clears = values[7];
+        stats.last_piece_cleared = values[8] != 0;
+        stats.piece_inputs = values[9];
+        stats.pieces.copy_from_slice(&values[10..17]);
+        stats.clears.copy_from_slice(&values[17..]);
+        Ok(stats)
+    }
+
+    // single line written along the highscores
+    pub fn to_record(&self) -> String {
+        format!("time={:.1} pieces={} pps={:.2} attack={} apm={:.1} kpp={:.2} faults={} max_combo={} \
+                 perfect_clears={} piece_counts={} clear_counts={}",
+                self.elapsed_secs(),
+                self.nb_pieces,
+                self.pieces_per_second(),
+                self.attack,
+                self.attack_per_minute(),
+                self.keys_per_piece(),
+                self.finesse_faults,
+                self.max_combo,
+                self.perfect_clears,
+                self.pieces.iter().map(|nb| nb.to_string()).collect::<Vec<_>>().join(","),
+                self.clears.iter().map(|nb| nb.to_string()).collect::<Vec<_>>().join(","))
+    }
+}
+
+impl GameListener for Stats {
+    fn on_event(&mut self, _tetris: &Tetris, event: &GameEvent) {
+        match *event {
+            GameEvent::PieceLocked { kind, x, state } => {
+                self.nb_pieces += 1;
+                self.pieces[kind as usize] += 1;
+                if let Some(optimal) = min_inputs(kind, x, state, self.nb_columns) {
+                    if self.piece_inputs > optimal {
+                        self.finesse_faults += 1;
+                    }
+                }
+                self.piece_inputs = 0;
+                if !self.last_piece_cleared {
+                    self.consecutive_clears = 0;
+                }
+                self.last_piece_cleared = false;
+            },
+            GameEvent::LinesCleared { kind, .. } => {
+                self.last_piece_cleared = true;
+                self.consecutive_clears += 1;
+                let combo = self.consecutive_clears as usize - 1;
+                if combo as u32 > self.max_combo {
+                    self.max_combo = combo as u32;
+                }
+
+                self.clears[kind as usize] += 1;
+                self.attack += ATTACK[kind as usize];
+                self.attack += COMBO_ATTACK[combo.min(COMBO_ATTACK.len() - 1)];
+            },
+            GameEvent::PerfectClear => {
+                self.perfect_clears += 1;
+                self.attack += PERFECT_CLEAR_ATTACK;
+            },
+            _ => {},
+        }
+    }
+}
@@ -1,31 +1,43 @@
-
-extern crate rand;
+use crate::board::Board;
 
 type Piece = Vec<Vec<u8>>;
 type States = Vec<Piece>;
 
 pub struct Tetrimino {
     pub states: States,
+    // bit masks of the lines of each state, for the collision tests
+    masks: Vec<[u16; 4]>,
     pub x: isize,
     pub y: usize,
     pub current_state: u8,
+    // whether the last successful action on the piece was a rotation (used for T-spins)
+    pub last_rotated: bool,
 }
 
+// piece kinds are stored in the maps as kind + 1, 0 being an empty case
+pub const PIECE_NAMES: [char; 7] = ['I', 'L', 'J', 'O', 'S', 'Z', 'T'];
+const T_KIND: u8 = 6;
+// position of the T center inside each of its states
+const T_CENTERS: [(isize, isize); 4] = [(1, 0), (1, 1), (1, 1), (0, 1)];
+
 trait TetriminoGenerator {
     fn new() -> Tetrimino;
 }
 
 
-pub fn create_new_tetrimino() -> Tetrimino {
-    static mut PREV: u8 = 7;
-    let mut rand_nb = rand::random::<u8>() % 7;
+pub fn kind_from_char(c: char) -> Option<u8> {
+    PIECE_NAMES.iter()
+        .position(|name| *name == c.to_ascii_uppercase())
+        .map(|kind| kind as u8)
+}
 
-    if unsafe{ PREV } == rand_nb {
-        rand_nb = rand::random::<u8>() % 7;
-    }
-    unsafe{ PREV = rand_nb };
+// column where the new pieces appear
+pub fn spawn_x(nb_columns: usize) -> isize {
+    nb_columns as isize / 2 - 1
+}
 
-    match rand_nb {
+pub fn create_tetrimino(kind: u8) -> Tetrimino {
+    match kind {
         0 => TetriminoI::new(),
         1 => TetriminoL::new(),
         2 => TetriminoJ::new(),
@@ -38,7 +50,31 @@ pub fn create_new_tetrimino() -> Tetrimino {
 }
 
 impl Tetrimino {
-    pub fn rotate(&mut self, game_map: &[Vec<u8>]) {
+    fn new(states: States) -> Tetrimino {
+        let masks = states.iter()
+            .map(|state| {
+                let mut mask = [0; 4];
+                for (line_nb, line) in state.iter().enumerate() {
+                    for (case_nb, case) in line.iter().enumerate() {
+                        if *case != 0 {
+                            mask[line_nb] |= 1 << case_nb;
+                        }
+                    }
+                }
+                mask
+            })
+            .collect();
+        Tetrimino {
+            states: states,
+            masks: masks,
+            x: 4,
+            y: 0,
+            current_state: 0,
+            last_rotated: false,
+        }
+    }
+
+    pub fn rotate(&mut self, game_map: &Board) {
         let mut tmp_state: u8 = self.current_state + 1;
         if tmp_state as usize >= self.states.len() {
             tmp_state = 0;
@@ -49,39 +85,64 @@ impl Tetrimino {
             if self.test_position(game_map, tmp_state as usize, self.x + x, self.y) {
                 self.current_state = tmp_state;
                 self.x += *x;
+                self.last_rotated = true;
                 break
             }
         }
     }
 
-    pub fn test_position(&self, game_map: &[Vec<u8>], tmp_state: usize, x: isize, y: usize) -> bool {
-        for decal_y in 0..4 {
-            for decal_x in 0..4 {
-                let x = x + decal_x;
-                if self.states[tmp_state][decal_y][decal_x as usize] != 0
-                    && (y + decal_y >= game_map.len()
-                        || x < 0
-                        || x as usize >= game_map[y + decal_y].len()
-                        || game_map[y + decal_y][x as usize] != 0 ) {
-                    return false;
-                }
-            }
-        }
-        return true;
+    pub fn test_position(&self, game_map: &Board, tmp_state: usize, x: isize, y: usize) -> bool {
+        game_map.fits(&self.masks[tmp_state], x, y)
     }
 
-    pub fn test_current_position(&self, game_map: &[Vec<u8>]) -> bool {
+    // number of lines the piece can fall from its current position
+    pub fn drop_distance(&self, game_map: &Board) -> usize {
+        game_map.drop_distance(&self.masks[self.current_state as usize], self.x, self.y)
+    }
+
+    pub fn test_current_position(&self, game_map: &Board) -> bool {
         self.test_position(game_map, self.current_state as usize, self.x, self.y)
     }
 
-    pub fn change_position(&mut self, game_map: &[Vec<u8>], new_x: isize, new_y: usize) -> bool {
+    pub fn change_position(&mut self, game_map: &Board, new_x: isize, new_y: usize) -> bool {
         if self.test_position(game_map, self.current_state as usize, new_x, new_y) {
+            if new_x != self.x || new_y != self.y {
+                self.last_rotated = false;
+            }
             self.x = new_x as isize;
             self.y = new_y;
             return true;
         }
         return false;
     }
+
+    pub fn kind(&self) -> u8 {
+        self.states[0].iter()
+            .flat_map(|line| line.iter())
+            .find(|case| **case != 0)
+            .map(|case| *case - 1)
+            .unwrap_or(0)
+    }
+
+    // 3-corner rule: a T locked right after a rotation with at least 3 of
+    // the cases diagonal to its center occupied (walls and floor count too)
+    pub fn is_t_spin(&self, game_map: &Board) -> bool {
+        if self.kind() != T_KIND || !self.last_rotated {
+            return false;
+        }
+        let (center_x, center_y) = T_CENTERS[self.current_state as usize];
+        let center_x = self.x + center_x;
+        let center_y = self.y as isize + center_y;
+        let mut corners = 0;
+        for (decal_x, decal_y) in [(-1, -1), (1, -1), (-1, 1), (1, 1)].iter() {
+            let x = center_x + decal_x;
+            let y = center_y + decal_y;
+            if y >= 0 && game_map.is_occupied(x, y) {
+                corners += 1;
+            }
+        }
+        corners >= 3
+    }
 }
 
 
@@ -89,20 +150,15 @@ struct TetriminoI;
 
 impl TetriminoGenerator for TetriminoI {
     fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![1, 1, 1, 1],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                         
-                         vec![vec![0, 1, 0, 0],
-                              vec![0, 1, 0, 0],
-                              vec![0, 1, 0, 0],
-                              vec![0, 1, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+        Tetrimino::new(vec![vec![vec![1, 1, 1, 1],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                            
+                            vec![vec![0, 1, 0, 0],
+                                 vec![0, 1, 0, 0],
+                                 vec![0, 1, 0, 0],
+                                 vec![0, 1, 0, 0]]])
     }
 }
 
@@ -111,30 +167,25 @@ struct TetriminoL;
 
 impl TetriminoGenerator for TetriminoL {
     fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![2, 2, 2, 0],
-                              vec![2, 0, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![2, 2, 0, 0],
-                              vec![0, 2, 0, 0],
-                              vec![0, 2, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![0, 0, 2, 0],
-                              vec![2, 2, 2, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![2, 0, 0, 0],
-                              vec![2, 0, 0, 0],
-                              vec![2, 2, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+        Tetrimino::new(vec![vec![vec![2, 2, 2, 0],
+                                 vec![2, 0, 0, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![2, 2, 0, 0],
+                                 vec![0, 2, 0, 0],
+                                 vec![0, 2, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![0, 0, 2, 0],
+                                 vec![2, 2, 2, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![2, 0, 0, 0],
+                                 vec![2, 0, 0, 0],
+                                 vec![2, 2, 0, 0],
+                                 vec![0, 0, 0, 0]]])
     }
 }
 
@@ -143,30 +194,25 @@ struct TetriminoJ;
 
 impl TetriminoGenerator for TetriminoJ {
     fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![3, 3, 3, 0],
-                              vec![0, 0, 3, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![0, 3, 0, 0],
-                              vec![0, 3, 0, 0],
-                              vec![3, 3, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![3, 0, 0, 0],
-                              vec![3, 3, 3, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![3, 3, 0, 0],
-                              vec![3, 0, 0, 0],
-                              vec![3, 0, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+        Tetrimino::new(vec![vec![vec![3, 3, 3, 0],
+                                 vec![0, 0, 3, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![0, 3, 0, 0],
+                                 vec![0, 3, 0, 0],
+                                 vec![3, 3, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![3, 0, 0, 0],
+                                 vec![3, 3, 3, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![3, 3, 0, 0],
+                                 vec![3, 0, 0, 0],
+                                 vec![3, 0, 0, 0],
+                                 vec![0, 0, 0, 0]]])
     }
 }
 
@@ -175,15 +221,10 @@ struct TetriminoO;
 
 impl TetriminoGenerator for TetriminoO {
     fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![4, 4, 0, 0],
-                              vec![4, 4, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+        Tetrimino::new(vec![vec![vec![4, 4, 0, 0],
+                                 vec![4, 4, 0, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]]])
     }
 }
 
@@ -192,20 +233,15 @@ struct TetriminoS;
 
 impl TetriminoGenerator for TetriminoS {
     fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![0, 5, 5, 0],
-                              vec![5, 5, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![5, 0, 0, 0],
-                              vec![5, 5, 0, 0],
-                              vec![0, 5, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+        Tetrimino::new(vec![vec![vec![0, 5, 5, 0],
+                                 vec![5, 5, 0, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![5, 0, 0, 0],
+                                 vec![5, 5, 0, 0],
+                                 vec![0, 5, 0, 0],
+                                 vec![0, 0, 0, 0]]])
     }
 }
 
@@ -214,20 +250,15 @@ struct TetriminoZ;
 
 impl TetriminoGenerator for TetriminoZ {
     fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![6, 6, 0, 0],
-                              vec![0, 6, 6, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![0, 6, 0, 0],
-                              vec![6, 6, 0, 0],
-                              vec![6, 0, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+        Tetrimino::new(vec![vec![vec![6, 6, 0, 0],
+                                 vec![0, 6, 6, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![0, 6, 0, 0],
+                                 vec![6, 6, 0, 0],
+                                 vec![6, 0, 0, 0],
+                                 vec![0, 0, 0, 0]]])
     }
 }
 
@@ -236,29 +267,24 @@ struct TetriminoT;
 
 impl TetriminoGenerator for TetriminoT {
     fn new() -> Tetrimino {
-        Tetrimino {
-            states: vec![vec![vec![7, 7, 7, 0],
-                              vec![0, 7, 0, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![0, 7, 0, 0],
-                              vec![7, 7, 0, 0],
-                              vec![0, 7, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![0, 7, 0, 0],
-                              vec![7, 7, 7, 0],
-                              vec![0, 0, 0, 0],
-                              vec![0, 0, 0, 0]],
-                              
-                         vec![vec![7, 0, 0, 0],
-                              vec![7, 7, 0, 0],
-                              vec![7, 0, 0, 0],
-                              vec![0, 0, 0, 0]]],
-            x: 4,
-            y: 0,
-            current_state: 0,
-        }
+        Tetrimino::new(vec![vec![vec![7, 7, 7, 0],
+                                 vec![0, 7, 0, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![0, 7, 0, 0],
+                                 vec![7, 7, 0, 0],
+                                 vec![0, 7, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![0, 7, 0, 0],
+                                 vec![7, 7, 7, 0],
+                                 vec![0, 0, 0, 0],
+                                 vec![0, 0, 0, 0]],
+                                 
+                            vec![vec![7, 0, 0, 0],
+                                 vec![7, 7, 0, 0],
+                                 vec![7, 0, 0, 0],
+                                 vec![0, 0, 0, 0]]])
     }
 }
\ No newline at end of file
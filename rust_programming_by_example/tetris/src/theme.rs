@@ -0,0 +1,371 @@
+use sdl2::image::LoadTexture;
+use sdl2::pixels::Color;
+use sdl2::rect::Rect;
+use sdl2::render::{Canvas, Texture, TextureCreator};
+use sdl2::ttf::{Font, Sdl2TtfContext};
+use sdl2::video::{Window, WindowContext};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{create_texture_rect, BLOCK_TEXTURE_SIZE};
+
+pub const THEMES_DIR: &'static str = "assets/themes";
+const THEME_FILE: &'static str = "theme.txt";
+pub const DEFAULT_FONT: &'static str = "assets/lucon.ttf";
+
+pub const DEFAULT_PALETTE: [Color; 7] = [Color { r: 255, g:  69, b:  69, a: 0xff },
+                                         Color { r: 255, g: 220, b:  69, a: 0xff },
+                                         Color { r: 237, g: 150, b:  37, a: 0xff },
+                                         Color { r: 171, g:  99, b: 137, a: 0xff },
+                                         Color { r:  77, g: 149, b: 239, a: 0xff },
+                                         Color { r:  39, g: 218, b: 225, a: 0xff },
+                                         Color { r:  45, g: 216, b:  47, a: 0xff }];
+
+// color of the garbage, and of every block with the monochrome modifier
+const GRAY_BLOCK_COLOR: Color = Color { r: 150, g: 150, b: 150, a: 0xff };
+
+// piece colors chosen to stay distinguishable with each kind of color blindness
+const DEUTERANOPIA_PALETTE: [Color; 7] = [Color { r:  86, g: 180, b: 233, a: 0xff },
+                                          Color { r: 230, g: 159, b:   0, a: 0xff },
+                                          Color { r:   0, g: 114, b: 178, a: 0xff },
+                                          Color { r: 240, g: 228, b:  66, a: 0xff },
+                                          Color { r:   0, g: 158, b: 115, a: 0xff },
+                                          Color { r: 213, g:  94, b:   0, a: 0xff },
+                                          Color { r: 204, g: 121, b: 167, a: 0xff }];
+const PROTANOPIA_PALETTE: [Color; 7] = [Color { r:  51, g: 187, b: 238, a: 0xff },
+                                        Color { r: 238, g: 119, b:  51, a: 0xff },
+                                        Color { r:   0, g: 119, b: 187, a: 0xff },
+                                        Color { r: 238, g: 221, b: 136, a: 0xff },
+                                        Color { r:   0, g: 153, b: 136, a: 0xff },
+                                        Color { r: 187, g: 187, b: 187, a: 0xff },
+                                        Color { r: 238, g:  51, b: 119, a: 0xff }];
+const TRITANOPIA_PALETTE: [Color; 7] = [Color { r:  94, g: 201, b: 226, a: 0xff },
+                                        Color { r: 232, g:  56, b:  79, a: 0xff },
+                                        Color { r:  27, g:  79, b: 114, a: 0xff },
+                                        Color { r: 244, g: 244, b: 244, a: 0xff },
+                                        Color { r:  60, g: 140, b:  60, a: 0xff },
+                                        Color { r: 139, g:  30, b:  63, a: 0xff },
+                                        Color { r: 255, g: 154, b: 162, a: 0xff }];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Palette {
+    // the colors defined by the theme
+    Theme,
+    Deuteranopia,
+    Protanopia,
+    Tritanopia,
+}
+
+impl Palette {
+    pub const ALL: [Palette; 4] = [Palette::Theme, Palette::Deuteranopia, Palette::Protanopia, Palette::Tritanopia];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Palette::Theme => "theme",
+            Palette::Deuteranopia => "deuteranopia",
+            Palette::Protanopia => "protanopia",
+            Palette::Tritanopia => "tritanopia",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Palette> {
+        Palette::ALL.iter().cloned().find(|palette| palette.name() == name)
+    }
+
+    fn colors(&self) -> Option<[Color; 7]> {
+        match *self {
+            Palette::Theme => None,
+            Palette::Deuteranopia => Some(DEUTERANOPIA_PALETTE),
+            Palette::Protanopia => Some(PROTANOPIA_PALETTE),
+            Palette::Tritanopia => Some(TRITANOPIA_PALETTE),
+        }
+    }
+}
+
+// accessibility settings applied on top of the theme
+#[derive(Clone, Copy)]
+pub struct DisplayOptions {
+    pub palette: Palette,
+    // draws a different glyph on each piece kind
+    pub patterns: bool,
+    // plain black background, white border and outlined blocks
+    pub high_contrast: bool,
+}
+
+// an image file or a plain color
+enum Fill {
+    Image(PathBuf),
+    Plain(Color),
+}
+
+// Everything needed to draw the game with a given look. Themes are
+// directories under THEMES_DIR containing a theme.txt file:
+//
+// name: Bevel
+// font: ../../lucon.ttf
+// blocks: blocks.png
+// background: ../../my_image.jpg
+// grid: 101018
+// border: ffffff
+// text: ffffff
+// highlight: ffdc45
+// palette: ff4545 ffdc45 ed9625 ab6389 4d95ef 27dae1 2dd82f
+//
+// Every entry is optional. Paths are relative to the theme directory,
+// `background`, `grid` and `border` accept either an image or a color. The block
+// sprite sheet is a row of square cells: either one per piece (in I L J O S
+// Z T order) or a single one that gets tinted with the palette colors.
+pub struct Theme<'a, 'ttf> {
+    pub name: String,
+    pub dir: PathBuf,
+    pub font: Font<'ttf, 'static>,
+    pub blocks: Vec<Texture<'a>>,
+    pub gray_block: Texture<'a>,
+    pub grid: Texture<'a>,
+    pub border: Texture<'a>,
+    pub background: Option<Texture<'a>>,
+    pub background_color: Color,
+    pub text_color: Color,
+    pub highlight_color: Color,
+}
+
+fn parse_color(text: &str) -> Option<Color> {
+    let text = text.trim_start_matches('#');
+    if text.len() != 6 {
+        return None
+    }
+    let value = u32::from_str_radix(text, 16).ok()?;
+    Some(Color::RGB((value >> 16) as u8, (value >> 8) as u8, value as u8))
+}
+
+fn parse_fill(dir: &Path, text: &str) -> Fill {
+    match parse_color(text) {
+        Some(color) => Fill::Plain(color),
+        None => Fill::Image(dir.join(text)),
+    }
+}
+
+fn parse_palette(text: &str) -> Result<[Color; 7], String> {
+    let colors = text.split_whitespace()
+                     .map(|color| parse_color(color).ok_or(format!("invalid color \"{}\"", color)))
+                     .collect::<Result<Vec<Color>, String>>()?;
+    if colors.len() != 7 {
+        return Err(format!("the palette needs 7 colors, {} given", colors.len()));
+    }
+    let mut palette = DEFAULT_PALETTE;
+    palette.copy_from_slice(&colors);
+    Ok(palette)
+}
+
+fn load_fill<'a>(canvas: &mut Canvas<Window>,
+                 texture_creator: &'a TextureCreator<WindowContext>,
+                 fill: &Fill) -> Result<Texture<'a>, String> {
+    match *fill {
+        Fill::Image(ref path) => texture_creator.load_texture(path)
+                                                .map_err(|e| format!("{}: {}", path.display(), e)),
+        Fill::Plain(color) => create_texture_rect(canvas, texture_creator, color, BLOCK_TEXTURE_SIZE, BLOCK_TEXTURE_SIZE)
+                                  .ok_or("couldn't create a texture".to_owned()),
+    }
+}
+
+// cuts the sprite sheet into one texture per piece
+fn load_blocks<'a>(canvas: &mut Canvas<Window>,
+                   texture_creator: &'a TextureCreator<WindowContext>,
+                   sheet_path: &Path,
+                   palette: &[Color; 7],
+                   options: &DisplayOptions) -> Result<Vec<Texture<'a>>, String> {
+    let mut sheet = texture_creator.load_texture(sheet_path)
+                                   .map_err(|e| format!("{}: {}", sheet_path.display(), e))?;
+    let query = sheet.query();
+    let nb_cells = query.width / query.height;
+    if nb_cells != 1 && nb_cells < 7 {
+        return Err(format!("{}: the sprite sheet needs 1 or 7 cells, found {}", sheet_path.display(), nb_cells));
+    }
+
+    let mut blocks = Vec::with_capacity(7);
+    for (kind, color) in palette.iter().enumerate() {
+        let cell = if nb_cells == 1 {
+            sheet.set_color_mod(color.r, color.g, color.b);
+            0
+        } else {
+            kind as i32
+        };
+        let mut block = texture_creator.create_texture_target(None, BLOCK_TEXTURE_SIZE, BLOCK_TEXTURE_SIZE)
+                                       .map_err(|e| e.to_string())?;
+        let mut result = Ok(());
+        canvas.with_texture_canvas(&mut block, |texture| {
+            texture.set_draw_color(Color::RGBA(0, 0, 0, 0));
+            texture.clear();
+            result = texture.copy(&sheet, Rect::new(cell * query.height as i32, 0, query.height, query.height), None);
+        }).map_err(|e| e.to_string())?;
+        result?;
+        decorate_block(canvas, &mut block, kind, *color, options)?;
+        blocks.push(block);
+    }
+    Ok(blocks)
+}
+
+fn pattern_rects(kind: usize) -> Vec<Rect> {
+    let size = BLOCK_TEXTURE_SIZE as i32;
+    let unit = size / 8;
+    match kind {
+        // I: horizontal bar
+        0 => vec![Rect::new(unit * 2, unit * 3, unit as u32 * 4, unit as u32 * 2)],
+        // L: corner at the bottom left
+        1 => vec![Rect::new(unit * 2, unit * 2, unit as u32, unit as u32 * 4),
+                  Rect::new(unit * 2, unit * 5, unit as u32 * 4, unit as u32)],
+        // J: corner at the bottom right
+        2 => vec![Rect::new(unit * 5, unit * 2, unit as u32, unit as u32 * 4),
+                  Rect::new(unit * 2, unit * 5, unit as u32 * 4, unit as u32)],
+        // O: hollow square
+        3 => vec![Rect::new(unit * 2, unit * 2, unit as u32 * 4, unit as u32),
+                  Rect::new(unit * 2, unit * 5, unit as u32 * 4, unit as u32),
+                  Rect::new(unit * 2, unit * 2, unit as u32, unit as u32 * 4),
+                  Rect::new(unit * 5, unit * 2, unit as u32, unit as u32 * 4)],
+        // S: rising diagonal
+        4 => (0..4).map(|step| Rect::new(unit * (2 + step), unit * (5 - step), unit as u32, unit as u32)).collect(),
+        // Z: falling diagonal
+        5 => (0..4).map(|step| Rect::new(unit * (2 + step), unit * (2 + step), unit as u32, unit as u32)).collect(),
+        // T: small T
+        _ => vec![Rect::new(unit * 2, unit * 2, unit as u32 * 4, unit as u32),
+                  Rect::new(unit * 3 + unit / 2, unit * 2, unit as u32, unit as u32 * 4)],
+    }
+}
+
+// draws the accessibility glyph and outline over a block texture
+fn decorate_block(canvas: &mut Canvas<Window>,
+                  block: &mut Texture,
+                  kind: usize,
+                  color: Color,
+                  options: &DisplayOptions) -> Result<(), String> {
+    if !options.patterns && !options.high_contrast {
+        return Ok(())
+    }
+    // dark glyphs on light colors, light glyphs on dark ones
+    let luminance = 299 * color.r as u32 + 587 * color.g as u32 + 114 * color.b as u32;
+    let glyph_color = if luminance > 140_000 { Color::RGB(0, 0, 0) } else { Color::RGB(255, 255, 255) };
+    let mut result = Ok(());
+    canvas.with_texture_canvas(block, |texture| {
+        if options.high_contrast {
+            let size = BLOCK_TEXTURE_SIZE;
+            let thickness = size / 10;
+            texture.set_draw_color(Color::RGB(255, 255, 255));
+            result = texture.fill_rects(&[Rect::new(0, 0, size, thickness),
+                                          Rect::new(0, (size - thickness) as i32, size, thickness),
+                                          Rect::new(0, 0, thickness, size),
+                                          Rect::new((size - thickness) as i32, 0, thickness, size)]);
+        }
+        if options.patterns && result.is_ok() {
+            texture.set_draw_color(glyph_color);
+            result = texture.fill_rects(&pattern_rects(kind));
+        }
+    }).map_err(|e| e.to_string())?;
+    result
+}
+
+pub fn load_theme<'a, 'ttf>(dir: &Path,
+                            canvas: &mut Canvas<Window>,
+                            texture_creator: &'a TextureCreator<WindowContext>,
+                            ttf_context: &'ttf Sdl2TtfContext,
+                            options: &DisplayOptions) -> Result<Theme<'a, 'ttf>, String> {
+    let content = fs::read_to_string(dir.join(THEME_FILE))
+        .map_err(|e| format!("{}: {}", dir.join(THEME_FILE).display(), e))?;
+
+    let mut name = dir.file_name()
+                      .map(|name| name.to_string_lossy().into_owned())
+                      .unwrap_or_default();
+    let mut font_path = PathBuf::from(DEFAULT_FONT);
+    let mut blocks_path = None;
+    let mut background = Fill::Plain(Color::RGB(0, 0, 255));
+    let mut grid = Fill::Plain(Color::RGB(0, 0, 0));
+    let mut border = Fill::Plain(Color::RGB(255, 255, 255));
+    let mut text_color = Color::RGB(255, 255, 255);
+    let mut highlight_color = Color::RGB(255, 220, 69);
+    let mut palette = DEFAULT_PALETTE;
+
+    for line in content.lines().map(|line| line.trim()).filter(|line| !line.is_empty()) {
+        let mut parts = line.splitn(2, ':');
+        let key = parts.next().unwrap_or("").trim();
+        let value = parts.next().unwrap_or("").trim();
+        let invalid_color = || format!("invalid color for {}: \"{}\"", key, value);
+        match key {
+            "name" => name = value.to_owned(),
+            "font" => font_path = dir.join(value),
+            "blocks" => blocks_path = Some(dir.join(value)),
+            "background" => background = parse_fill(dir, value),
+            "grid" => grid = parse_fill(dir, value),
+            "border" => border = parse_fill(dir, value),
+            "text" => text_color = parse_color(value).ok_or_else(invalid_color)?,
+            "highlight" => highlight_color = parse_color(value).ok_or_else(invalid_color)?,
+            "palette" => palette = parse_palette(value)?,
+            _ => return Err(format!("{}: unknown entry \"{}\"", dir.display(), key)),
+        }
+    }
+
+    if let Some(colors) = options.palette.colors() {
+        palette = colors;
+    }
+    if options.high_contrast {
+        background = Fill::Plain(Color::RGB(0, 0, 0));
+        grid = Fill::Plain(Color::RGB(0, 0, 0));
+        border = Fill::Plain(Color::RGB(255, 255, 255));
+        text_color = Color::RGB(255, 255, 255);
+        highlight_color = Color::RGB(255, 255, 0);
+    }
+
+    let font = ttf_context.load_font(&font_path, 64)
+                          .map_err(|e| format!("{}: {}", font_path.display(), e))?;
+    let blocks = match blocks_path {
+        Some(ref path) => load_blocks(canvas, texture_creator, path, &palette, options)?,
+        None => {
+            let mut blocks = Vec::with_capacity(7);
+            for (kind, color) in palette.iter().enumerate() {
+                let mut block = load_fill(canvas, texture_creator, &Fill::Plain(*color))?;
+                decorate_block(canvas, &mut block, kind, *color, options)?;
+                blocks.push(block);
+            }
+            blocks
+        },
+    };
+    let (background_color, background) = match background {
+        Fill::Plain(color) => (color, None),
+        ref image => (Color::RGB(0, 0, 0), Some(load_fill(canvas, texture_creator, image)?)),
+    };
+
+    Ok(Theme {
+        name: name,
+        dir: dir.to_owned(),
+        font: font,
+        blocks: blocks,
+        gray_block: load_fill(canvas, texture_creator, &Fill::Plain(GRAY_BLOCK_COLOR))?,
+        grid: load_fill(canvas, texture_creator, &grid)?,
+        border: load_fill(canvas, texture_creator, &border)?,
+        background: background,
+        background_color: background_color,
+        text_color: text_color,
+        highlight_color: highlight_color,
+    })
+}
+
+pub fn list_themes() -> Vec<PathBuf> {
+    let mut dirs = match fs::read_dir(THEMES_DIR) {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                              .filter(|path| path.join(THEME_FILE).is_file())
+                              .collect::<Vec<PathBuf>>(),
+        Err(_) => Vec::new(),
+    };
+    dirs.sort();
+    dirs
+}
+
+impl<'a, 'ttf> Theme<'a, 'ttf> {
+    pub fn draw_background(&self, canvas: &mut Canvas<Window>) {
+        canvas.set_draw_color(self.background_color);
+        canvas.clear();
+        if let Some(ref background) = self.background {
+            canvas.copy(background, None, None)
+                .expect("Couldn't copy the background texture into window");
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use crate::board::Board;
+use crate::finesse::{footprint, min_input_sequence};
+use crate::game_board::Input;
+use crate::randomizer::Randomizer;
+use crate::tetrimino::{create_tetrimino, PIECE_NAMES};
+
+// mixed into the game seed for the targets
+const TRAINER_SEED: u64 = 0x2f6b_8e1d_4c73_a905;
+
+// Where the current piece has to go, with the shortest way to get there.
+#[derive(Clone)]
+pub struct Target {
+    pub kind: u8,
+    pub x: isize,
+    pub state: u8,
+    pub inputs: Vec<Input>,
+}
+
+impl Target {
+    // leftmost column covered by the piece
+    pub fn column(&self) -> usize {
+        footprint(self.kind, self.x, self.state).iter().map(|(x, _)| *x).min().unwrap_or(0) as usize
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TrainerResult {
+    Optimal,
+    TooManyInputs { used: u32, needed: u32 },
+    WrongPlacement,
+}
+
+impl TrainerResult {
+    pub fn description(&self) -> String {
+        match *self {
+            TrainerResult::Optimal => "Optimal".to_owned(),
+            TrainerResult::TooManyInputs { used, needed } => format!("{} inputs, {} needed", used, needed),
+            TrainerResult::WrongPlacement => "Wrong placement".to_owned(),
+        }
+    }
+}
+
+// Finesse training: every piece has a target placement on an empty board,
+// and comes back until it has been placed there with the fewest inputs.
+#[derive(Clone)]
+pub struct FinesseTrainer {
+    pub target: Option<Target>,
+    // horizontal moves and rotations done with the current piece
+    pub piece_inputs: u32,
+    pub last_result: Option<TrainerResult>,
+    // attempts and optimal placements, by piece kind and by target column
+    pub piece_attempts: [u32; 7],
+    pub piece_optimal: [u32; 7],
+    pub column_attempts: Vec<u32>,
+    pub column_optimal: Vec<u32>,
+    randomizer: Randomizer,
+    nb_columns: usize,
+}
+
+fn accuracy(optimal: u32, attempts: u32) -> f64 {
+    if attempts > 0 { optimal as f64 * 100. / attempts as f64 } else { 0. }
+}
+
+impl FinesseTrainer {
+    pub fn new(seed: u64, nb_columns: usize) -> FinesseTrainer {
+        FinesseTrainer {
+            target: None,
+            piece_inputs: 0,
+            last_result: None,
+            piece_attempts: [0; 7],
+            piece_optimal: [0; 7],
+            column_attempts: vec![0; nb_columns],
+            column_optimal: vec![0; nb_columns],
+            randomizer: Randomizer::new(seed ^ TRAINER_SEED),
+            nb_columns: nb_columns,
+        }
+    }
+
+    fn pick_target(&mut self, kind: u8) -> Target {
+        let game_map = Board::new(self.nb_columns, 4);
+        let piece = create_tetrimino(kind);
+        let state = self.randomizer.next_below(piece.states.len());
+        let columns = (-3..self.nb_columns as isize)
+            .filter(|x| piece.test_position(&game_map, state, *x, 0))
+            .collect::<Vec<_>>();
+        let x = columns[self.randomizer.next_below(columns.len())];
+        Target {
+            kind: kind,
+            x: x,
+            state: state as u8,
+            inputs: min_input_sequence(kind, x, state as u8, self.nb_columns).unwrap_or_default(),
+        }
+    }
+
+    // Called when a piece appears. The target stays until it has been done
+    // optimally, a held piece getting one of its own.
+    pub fn start_piece(&mut self, kind: u8) {
+        if self.target.as_ref().is_none_or(|target| target.kind != kind) {
+            self.target = Some(self.pick_target(kind));
+        }
+        self.piece_inputs = 0;
+    }
+
+    // counted as the stats do, the auto shift following a tap of the same key
+    pub fn record_input(&mut self, input: Input) {
+        match input {
+            Input::Left | Input::Right | Input::Rotate => self.piece_inputs += 1,
+            Input::DasLeft | Input::DasRight | Input::RepeatLeft | Input::RepeatRight |
+            Input::SoftDrop | Input::HardDrop | Input::Hold => {},
+        }
+    }
+
+    // Checks a locked piece against the target. Returns whether the piece has
+    // been done, the target being forgotten then.
+    pub fn evaluate(&mut self, kind: u8, x: isize, state: u8) -> bool {
+        let target = match self.target {
+            Some(ref target) if target.kind == kind => target.clone(),
+            _ => return false,
+        };
+        let needed = target.inputs.len() as u32;
+        let result = if footprint(kind, x, state) != footprint(kind, target.x, target.state) {
+            TrainerResult::WrongPlacement
+        } else if self.piece_inputs > needed {
+            TrainerResult::TooManyInputs { used: self.piece_inputs, needed: needed }
+        } else {
+            TrainerResult::Optimal
+        };
+        let column = target.column().min(self.nb_columns - 1);
+        self.piece_attempts[kind as usize] += 1;
+        self.column_attempts[column] += 1;
+        if result == TrainerResult::Optimal {
+            self.piece_optimal[kind as usize] += 1;
+            self.column_optimal[column] += 1;
+            self.target = None;
+        }
+        self.last_result = Some(result);
+        self.piece_inputs = 0;
+        result == TrainerResult::Optimal
+    }
+
+    pub fn accuracy(&self) -> f64 {
+        accuracy(self.piece_optimal.iter().sum(), self.piece_attempts.iter().sum())
+    }
+
+    // short lines shown next to the grid while training
+    pub fn live_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Accuracy: {:.0}%", self.accuracy())];
+        if let Some(ref target) = self.target {
+            lines.push(format!("Needs: {} inputs", target.inputs.len()));
+        }
+        if let Some(result) = self.last_result {
+            lines.push(result.description());
+        }
+        lines
+    }
+
+    // accuracy by piece and by column, for the results screen
+    pub fn summary_lines(&self) -> Vec<String> {
+        let mut lines = vec![format!("Accuracy: {:.1}%", self.accuracy())];
+        lines.push(PIECE_NAMES.iter()
+                              .enumerate()
+                              .filter(|(kind, _)| self.piece_attempts[*kind] > 0)
+                              .map(|(kind, name)| {
+                                  format!("{}:{:.0}%", name, accuracy(self.piece_optimal[kind], self.piece_attempts[kind]))
+                              })
+                              .collect::<Vec<_>>()
+                              .join(" "));
+        lines.push(format!("Columns: {}", (0..self.nb_columns)
+                                             .map(|column| {
+                                                 if self.column_attempts[column] > 0 {
+                                                     format!("{:.0}", accuracy(self.column_optimal[column], self.column_attempts[column]))
+                                                 } else {
+                                                     "-".to_owned()
+                                                 }
+                                             })
+                                             .collect::<Vec<_>>()
+                                             .join(" ")));
+        lines
+    }
+}